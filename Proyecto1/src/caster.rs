@@ -1,60 +1,400 @@
 // caster.rs
-use sdl2::render::{WindowCanvas, Texture};
-use sdl2::rect::Rect;
-use crate::maze;
+use sdl2::pixels::Color;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use crate::fog::Fog;
+use crate::framebuffer::{Framebuffer, Image};
+use crate::light::LightMap;
+use crate::maze::Maze;
 use crate::player::Player;
+use crate::segment::Point;
+use crate::tiles::{Face, TileSet};
 
+// Altura de los ojos sobre el piso en que está parado el jugador
+const EYE_HEIGHT: f64 = 0.5;
+
+// Cámara del jugador: posición, altura del piso bajo sus pies (ver Player::z), ángulo
+// y plano de cámara (perpendicular a la mirada, de largo tan(fov / 2))
+#[derive(Clone, Copy)]
+pub struct View {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    pub angle: f64,
+    pub plane_x: f64,
+    pub plane_y: f64,
+}
+
+impl View {
+    pub fn new(player: &Player, fov: f64) -> Self {
+        let half = (fov / 2.0).tan();
+        View {
+            x: player.x,
+            y: player.y,
+            z: player.z,
+            angle: player.dir_angle,
+            plane_x: -player.dir_angle.sin() * half,
+            plane_y: player.dir_angle.cos() * half,
+        }
+    }
+}
+
+// Niebla y mapa de luz del piso que se dibuja (cualquiera puede faltar)
+#[derive(Clone, Copy, Default)]
+pub struct Environment<'a> {
+    pub fog: Option<&'a Fog>,
+    pub lights: Option<&'a LightMap>,
+}
+
+// Imágenes de una tabla de casillas. Cada archivo se carga una sola vez
+// aunque lo usen varias caras o casillas.
+#[derive(Default)]
+struct ImageCache {
+    images: Vec<Image>,
+    loaded: HashMap<PathBuf, usize>,
+}
+
+impl ImageCache {
+    fn load(&mut self, path: &Path) -> Result<usize, String> {
+        if let Some(&index) = self.loaded.get(path) {
+            return Ok(index);
+        }
+        self.images.push(Image::load(path)?);
+        self.loaded.insert(path.to_path_buf(), self.images.len() - 1);
+        Ok(self.images.len() - 1)
+    }
+}
+
+// Texturas de pared de una tabla de casillas
+pub struct WallTextures {
+    images: Vec<Image>,
+    faces: Vec<[Option<usize>; 4]>,   // por id de casilla y Face::index
+}
+
+impl WallTextures {
+    pub fn load(tiles: &TileSet) -> Result<Self, String> {
+        let mut cache = ImageCache::default();
+        let mut faces = vec![[None; 4]; 256];
+
+        for tile in tiles.iter() {
+            for face in Face::ALL {
+                let Some(path) = tiles.texture_for(tile.id, face) else { continue };
+                faces[tile.id as usize][face.index()] = Some(cache.load(path)?);
+            }
+        }
+
+        Ok(WallTextures { images: cache.images, faces })
+    }
+
+    fn get(&self, tile: u8, face: Face) -> Option<&Image> {
+        let index = self.faces[tile as usize][face.index()]?;
+        self.images.get(index)
+    }
+}
+
+// Texturas de piso y techo de una tabla de casillas
+pub struct FloorTextures {
+    images: Vec<Image>,
+    floor: Vec<Option<usize>>,     // por id de casilla
+    ceiling: Vec<Option<usize>>,
+}
+
+impl FloorTextures {
+    pub fn load(tiles: &TileSet) -> Result<Self, String> {
+        let mut cache = ImageCache::default();
+        let mut floor = vec![None; 256];
+        let mut ceiling = vec![None; 256];
+        for tile in tiles.iter() {
+            if let Some(path) = &tile.floor_texture {
+                floor[tile.id as usize] = Some(cache.load(path)?);
+            }
+            if let Some(path) = &tile.ceiling_texture {
+                ceiling[tile.id as usize] = Some(cache.load(path)?);
+            }
+        }
+
+        Ok(FloorTextures { images: cache.images, floor, ceiling })
+    }
+
+    // Sin texturas de piso ni techo alcanza con el fondo liso (framebuffer::draw_background)
+    pub fn is_empty(&self) -> bool {
+        self.images.is_empty()
+    }
+}
+
+// Piso (z = 0) y techo (z = 1) pixel por pixel. Las casillas sin textura de techo dejan ver
+// `sky` y las sin textura de piso usan `floor_color`; con transparencia se mezclan con esos colores.
+// Cada pixel toma la luz de su casilla y la niebla de su distancia.
+pub fn render_planes(
+    framebuffer: &mut Framebuffer,
+    maze: &Maze,
+    player: &Player,
+    floor_textures: &FloorTextures,
+    sky: Color,
+    floor_color: Color,
+    Environment { fog, lights }: Environment,
+) {
+    let (width, height) = (framebuffer.width as usize, framebuffer.height as usize);
+    let eye = player.z + EYE_HEIGHT;
+    let half = height as f64 / 2.0;
+    let fov = std::f64::consts::PI / 3.0;
+
+    // Dirección de cada columna (los mismos rayos que render_scene), escalada para que
+    // multiplicada por la distancia perpendicular dé el punto del plano
+    let columns: Vec<(f64, f64)> = (0..width)
+        .map(|i| {
+            let offset = (2.0 * i as f64 / width as f64 - 1.0) * (fov / 2.0);
+            let angle = player.dir_angle + offset;
+            (angle.cos() / offset.cos(), angle.sin() / offset.cos())
+        })
+        .collect();
+
+    for y in 0..height {
+        let is_ceiling = (y as f64) < half;
+        let (plane, rows, base) = if is_ceiling {
+            (1.0 - eye, half - y as f64 - 0.5, sky)
+        } else {
+            (eye, y as f64 + 0.5 - half, floor_color)
+        };
+        let distance = plane * height as f64 / rows;
+        let textures = if is_ceiling { &floor_textures.ceiling } else { &floor_textures.floor };
+
+        for (x, &(dx, dy)) in columns.iter().enumerate() {
+            // Sin plano (ojos por encima del techo) no hay punto ni distancia: color liso
+            if plane <= 0.0 {
+                framebuffer.draw_pixel(x as i32, y as i32, base);
+                continue;
+            }
+            let (px, py) = (player.x + dx * distance, player.y + dy * distance);
+            let shade = |color: Color| {
+                let color = lights.map_or(color, |lights| lights.apply(color, px, py));
+                fog.map_or(color, |fog| fog.apply(color, distance))
+            };
+            // El cielo abierto no recibe luz, solo niebla
+            let open = if is_ceiling { fog.map_or(base, |fog| fog.apply(base, distance)) } else { shade(base) };
+            framebuffer.draw_pixel(x as i32, y as i32, open);
+            let texture = maze.get(px.floor() as i32, py.floor() as i32)
+                .and_then(|tile| textures[tile as usize]);
+            if let Some(index) = texture {
+                let color = floor_textures.images[index].sample(px - px.floor(), py - py.floor());
+                framebuffer.draw_pixel(x as i32, y as i32, shade(color));
+            }
+        }
+    }
+}
+
+// Lo que encuentra un rayo al chocar con una casilla
+struct RayHit {
+    distance: f64,
+    tile: u8,
+    hit_x: f64,     // posición horizontal en la cara (0..1)
+    face: Face,
+    bottom: f64,    // alturas de la cara en el mundo (0 = piso, 1 = techo de una pared normal)
+    top: f64,
+}
+
+// Filas de una columna de pantalla ya tapadas por paredes opacas (intervalos ordenados y separados)
+#[derive(Default)]
+struct Coverage {
+    spans: Vec<(i32, i32)>,
+}
+
+impl Coverage {
+    fn add(&mut self, top: i32, bottom: i32) {
+        if top >= bottom {
+            return;
+        }
+        self.spans.push((top, bottom));
+        self.spans.sort_unstable();
+        let mut merged: Vec<(i32, i32)> = Vec::with_capacity(self.spans.len());
+        for &(top, bottom) in &self.spans {
+            match merged.last_mut() {
+                Some(last) if top <= last.1 => last.1 = last.1.max(bottom),
+                _ => merged.push((top, bottom)),
+            }
+        }
+        self.spans = merged;
+    }
+
+    fn covers(&self, top: i32, bottom: i32) -> bool {
+        self.spans.iter().any(|&(a, b)| a <= top && b >= bottom)
+    }
+}
+
+// Base y tope de la pared de una casilla
+fn wall_span(maze: &Maze, tile: u8) -> (f64, f64) {
+    maze.tiles().get(tile).map_or((0.0, 1.0), |tile| (tile.elevation, tile.elevation + tile.height))
+}
+
+// Tope más alto entre las casillas que hay en el mapa (incluidas las paredes secretas en movimiento)
+fn max_top(maze: &Maze) -> f64 {
+    let mut top: f64 = 0.0;
+    for y in 0..maze.height() as i32 {
+        for x in 0..maze.width() as i32 {
+            let tiles = maze.get(x, y).into_iter().chain(maze.moving_wall(x, y).map(|(_, tile)| tile));
+            for tile in tiles {
+                top = top.max(wall_span(maze, tile).1);
+            }
+        }
+    }
+    top
+}
+
+// Dibuja las paredes y anota en el framebuffer, por pixel, la distancia perpendicular a la cámara
+// de la pared opaca más cercana que se dibujó ahí (ver Framebuffer::depth). Así un sprite
+// detrás de un muro bajo o de un escalón se tapa solo en las filas que ocupa el muro.
 pub fn render_scene(
-    canvas: &mut WindowCanvas,
+    framebuffer: &mut Framebuffer,
+    maze: &Maze,
     player: &Player,
-    wall_textures: &[Texture],
-    screen_width: u32,
-    screen_height: u32,
-) -> Result<(), String> {
+    wall_textures: &WallTextures,
+    side_shade: f64,   // brillo de las caras norte/sur (1.0 = sin sombra)
+    Environment { fog, lights }: Environment,
+) {
+    let (screen_width, screen_height) = (framebuffer.width, framebuffer.height);
+    let shade = (side_shade.clamp(0.0, 1.0) * 255.0) as u8;
+    let eye = player.z + EYE_HEIGHT;
+    let half = screen_height as f64 / 2.0;
+    let max_top = max_top(maze);
+
     let num_rays = screen_width;
     let fov = std::f64::consts::PI / 3.0;
+    framebuffer.clear_depth();
     
     for i in 0..num_rays {
         let camera_x = 2.0 * i as f64 / num_rays as f64 - 1.0;
         let ray_angle = player.dir_angle + camera_x * (fov / 2.0);
-        
-        let (distance, wall_type, hit_x) = cast_ray(player, ray_angle);
-        
-        if distance > 0.0 && distance < f64::INFINITY {
-            // Corregir distancia por fish-eye
-            let corrected_distance = distance * (ray_angle - player.dir_angle).cos();
-            
-            let wall_height = (screen_height as f64 / corrected_distance) as i32;
-            let draw_start = ((screen_height as i32 - wall_height) / 2).max(0);
-            let draw_end = ((screen_height as i32 + wall_height) / 2).min(screen_height as i32);
-            
-            if wall_type > 0 && wall_type <= wall_textures.len() as u8 {
-                let texture = &wall_textures[(wall_type - 1) as usize];
-                let tex_query = texture.query();
-                let tex_x = (hit_x * tex_query.width as f64) as i32 % tex_query.width as i32;
-                
-                let src_rect = Rect::new(tex_x, 0, 1, tex_query.height);
-                let dst_rect = Rect::new(
-                    i as i32,
-                    draw_start,
-                    1,
-                    (draw_end - draw_start) as u32
-                );
-                
-                canvas.copy(texture, src_rect, dst_rect)?;
+        // Fila de pantalla de la altura z a una distancia, y distancia corregida por fish-eye
+        // y filas que ocupa una cara
+        let screen_y = |distance: f64, z: f64| (half - (z - eye) * screen_height as f64 / distance) as i32;
+        let project = |hit: &RayHit| {
+            let corrected_distance = (hit.distance * (ray_angle - player.dir_angle).cos()).max(0.01);
+            (
+                corrected_distance,
+                screen_y(corrected_distance, hit.top).max(0),
+                screen_y(corrected_distance, hit.bottom).min(screen_height as i32),
+            )
+        };
+
+        // Detrás de una cara todo queda más cerca del horizonte, entre las filas del tope más alto
+        // del mapa y del piso a esa distancia: el rayo sigue hasta que eso ya está tapado
+        let mut covered = Coverage::default();
+        let horizon = half as i32;
+        let hits = cast_ray(maze, (player.x, player.y), ray_angle, |hit| {
+            let transparent = maze.tiles().get(hit.tile).is_some_and(|tile| tile.transparent);
+            if hit.distance <= 0.0 || transparent {
+                return false;
+            }
+            let (distance, top, bottom) = project(hit);
+            covered.add(top, bottom);
+            let behind_top = screen_y(distance, max_top).min(horizon).max(0);
+            let behind_bottom = screen_y(distance, 0.0).max(horizon).min(screen_height as i32);
+            covered.covers(behind_top, behind_bottom)
+        });
+
+        // De lejos a cerca: lo cercano (paredes bajas, transparentes) queda encima de lo que hay detrás
+        for hit in hits.iter().rev() {
+            if hit.distance <= 0.0 {
+                continue;
+            }
+
+            let (corrected_distance, top, bottom) = project(hit);
+
+            // La luz de la casilla desde la que se ve la cara (justo antes de chocar)
+            let front = hit.distance - 0.01;
+            let (light_x, light_y) = (player.x + ray_angle.cos() * front, player.y + ray_angle.sin() * front);
+
+            // Las caras norte/sur más oscuras para que las esquinas se distingan, y la niebla encima
+            let dim = |color: Color| {
+                let color = lights.map_or(color, |lights| lights.apply(color, light_x, light_y));
+                let color = match hit.face {
+                    Face::North | Face::South => {
+                        let scale = |c: u8| (c as u16 * shade as u16 / 255) as u8;
+                        Color::RGBA(scale(color.r), scale(color.g), scale(color.b), color.a)
+                    }
+                    Face::East | Face::West => color,
+                };
+                fog.map_or(color, |fog| fog.apply(color, corrected_distance))
+            };
+
+            // Los pixeles opacos tapan a los sprites que estén más lejos (ver draw_sprites)
+            let mut draw = |y: i32, color: Color| {
+                if color.a == 255 {
+                    framebuffer.mark_depth(i as i32, y, corrected_distance);
+                }
+                framebuffer.draw_pixel(i as i32, y, dim(color));
+            };
+            match wall_textures.get(hit.tile, hit.face) {
+                Some(texture) => {
+                    for y in top..bottom {
+                        // Altura del mundo que se ve en esta fila; la textura se repite
+                        // cada unidad de altura, alineada con el piso del mundo
+                        let z = eye + (half - y as f64 - 0.5) * corrected_distance / screen_height as f64;
+                        draw(y, texture.sample(hit.hit_x, z.ceil() - z));
+                    }
+                }
+                None => {
+                    // Frente de un piso elevado sin textura: color del minimapa
+                    let (r, g, b) = maze.tiles().get(hit.tile).map_or((128, 128, 128), |tile| tile.minimap);
+                    for y in top..bottom {
+                        draw(y, Color::RGB(r, g, b));
+                    }
+                }
             }
         }
     }
-    
-    Ok(())
 }
 
-fn cast_ray(player: &Player, angle: f64) -> (f64, u8, f64) {
+// Pared opaca que va del piso al techo: tapa la luz (ver line_of_sight)
+fn blocks_view(maze: &Maze, hit: &RayHit) -> bool {
+    let transparent = maze.tiles().get(hit.tile).is_some_and(|tile| tile.transparent);
+    !transparent && hit.bottom <= 0.0 && hit.top >= 1.0
+}
+
+// Si desde `from` se llega a `to` sin cruzar una pared entera (con los mismos rayos que las paredes)
+pub fn line_of_sight(maze: &Maze, from: Point, to: Point) -> bool {
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let distance = (dx * dx + dy * dy).sqrt();
+    if distance < 1e-9 {
+        return true;
+    }
+    !cast_ray(maze, from, dy.atan2(dx), |hit| blocks_view(maze, hit))
+        .iter()
+        .any(|hit| hit.distance < distance && blocks_view(maze, hit))
+}
+
+// Paredes finas o diagonales de la casilla que cruza el rayo, de la más cercana a la más lejana
+fn segment_hits(maze: &Maze, origin: Point, ray_dir: (f64, f64), (x, y): (i32, i32)) -> Vec<RayHit> {
+    let Some(tile) = maze.get(x, y) else { return Vec::new() };
+    let (bottom, top) = wall_span(maze, tile);
+    let mut hits: Vec<RayHit> = maze.segments(x, y)
+        .filter_map(|segment| {
+            let (distance, along) = segment.ray_hit(origin, ray_dir)?;
+            // La cara depende de qué lado del segmento viene el rayo
+            let face = if segment.is_horizontal() {
+                if ray_dir.1 > 0.0 { Face::North } else { Face::South }
+            } else if ray_dir.0 > 0.0 {
+                Face::West
+            } else {
+                Face::East
+            };
+            let hit_x = (along * segment.length()).fract();
+            Some(RayHit { distance, tile, hit_x, face, bottom, top })
+        })
+        .collect();
+    hits.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+    hits
+}
+
+// Casillas que toca un rayo, de la más cercana a la más lejana.
+// Sigue de largo después de paredes bajas o transparentes y de los frentes de pisos elevados;
+// se detiene en la primera cara para la que `stop` da true (ya no se ve nada detrás).
+fn cast_ray(maze: &Maze, origin: Point, angle: f64, mut stop: impl FnMut(&RayHit) -> bool) -> Vec<RayHit> {
     let ray_dir_x = angle.cos();
     let ray_dir_y = angle.sin();
     
-    let mut map_x = player.x as i32;
-    let mut map_y = player.y as i32;
+    let mut map_x = origin.0 as i32;
+    let mut map_y = origin.1 as i32;
     
     let delta_dist_x = if ray_dir_x.abs() < 1e-10 { 
         f64::INFINITY 
@@ -69,68 +409,214 @@ fn cast_ray(player: &Player, angle: f64) -> (f64, u8, f64) {
     };
     
     let (step_x, mut side_dist_x) = if ray_dir_x < 0.0 {
-        (-1, (player.x - map_x as f64) * delta_dist_x)
+        (-1, (origin.0 - map_x as f64) * delta_dist_x)
     } else {
-        (1, (map_x as f64 + 1.0 - player.x) * delta_dist_x)
+        (1, (map_x as f64 + 1.0 - origin.0) * delta_dist_x)
     };
     
     let (step_y, mut side_dist_y) = if ray_dir_y < 0.0 {
-        (-1, (player.y - map_y as f64) * delta_dist_y)
+        (-1, (origin.1 - map_y as f64) * delta_dist_y)
     } else {
-        (1, (map_y as f64 + 1.0 - player.y) * delta_dist_y)
+        (1, (map_y as f64 + 1.0 - origin.1) * delta_dist_y)
     };
     
-    let mut hit = false;
-    let mut side = 0;
-    let mut wall_type = 0u8;
-    let max_iterations = 100; // Prevenir loops infinitos
-    let mut iterations = 0;
+    let mut hits = Vec::new();
+    // Altura del último piso que cruzó el rayo, para ver los escalones que suben
+    let mut floor_z = maze.elevation(map_x, map_y);
+
+    let max_iterations = maze.width() + maze.height(); // Prevenir loops infinitos
     
-    while !hit && iterations < max_iterations {
-        iterations += 1;
-        
-        if side_dist_x < side_dist_y {
+    for _ in 0..max_iterations {
+        // Paredes finas o diagonales de la casilla en que está el rayo (antes de salir de ella)
+        for hit in segment_hits(maze, origin, (ray_dir_x, ray_dir_y), (map_x, map_y)) {
+            let done = stop(&hit);
+            hits.push(hit);
+            if done {
+                return hits;
+            }
+        }
+
+        let side = if side_dist_x < side_dist_y {
             side_dist_x += delta_dist_x;
             map_x += step_x;
-            side = 0;
+            0
         } else {
             side_dist_y += delta_dist_y;
             map_y += step_y;
-            side = 1;
-        }
+            1
+        };
         
         // Verificar límites del mapa
-        if map_x < 0 || map_x >= maze::MAP_WIDTH as i32 || 
-           map_y < 0 || map_y >= maze::MAP_HEIGHT as i32 {
-            return (f64::INFINITY, 0, 0.0);
-        }
+        let wall_type = match maze.get(map_x, map_y) {
+            Some(cell) => cell,
+            None => break,
+        };
+        let (bottom, top) = wall_span(maze, wall_type);
+
+        // Borde por el que el rayo entra a la casilla
+        let distance = if side == 0 {
+            (map_x as f64 - origin.0 + (1.0 - step_x as f64) / 2.0) / ray_dir_x
+        } else {
+            (map_y as f64 - origin.1 + (1.0 - step_y as f64) / 2.0) / ray_dir_y
+        };
+        
+        let wall_x = if side == 0 {
+            origin.1 + distance * ray_dir_y
+        } else {
+            origin.0 + distance * ray_dir_x
+        };
         
-        unsafe {
-            wall_type = maze::MAP[map_y as usize][map_x as usize];
-            // Una pared es cualquier valor diferente de 0 y diferente de 5 (meta)
-            if wall_type != 0 && wall_type != 5 {
-                hit = true;
+        // El rayo entra a la casilla por la cara opuesta a su dirección
+        let face = match (side, step_x, step_y) {
+            (0, 1, _) => Face::West,
+            (0, _, _) => Face::East,
+            (_, _, 1) => Face::North,
+            _ => Face::South,
+        };
+        let hit_x = wall_x - wall_x.floor();
+
+        // Pared secreta en movimiento: un bloque desplazado que puede ocupar dos casillas.
+        // Se dibuja solo si el rayo entra al bloque dentro de esta casilla.
+        if let Some(((bx, by), tile)) = maze.moving_wall(map_x, map_y) {
+            if let Some(hit) = box_hit(origin, ray_dir_x, ray_dir_y, (bx, by), tile, wall_span(maze, tile)) {
+                let inside_x = origin.0 + ray_dir_x * (hit.distance + 1e-6);
+                let inside_y = origin.1 + ray_dir_y * (hit.distance + 1e-6);
+                if inside_x.floor() as i32 == map_x && inside_y.floor() as i32 == map_y {
+                    let done = stop(&hit);
+                    hits.push(hit);
+                    if done {
+                        break;
+                    }
+                }
             }
+            continue;
+        }
+
+        // Puerta: plano en la mitad de la casilla, entre las dos paredes que la rodean
+        if maze.is_door(map_x, map_y) {
+            let across_x = maze.is_solid(map_x, map_y - 1) && maze.is_solid(map_x, map_y + 1);
+            let (distance, along, cell, face) = if across_x {
+                let t = (map_x as f64 + 0.5 - origin.0) / ray_dir_x;
+                (t, origin.1 + t * ray_dir_y, map_y, if step_x > 0 { Face::West } else { Face::East })
+            } else {
+                let t = (map_y as f64 + 0.5 - origin.1) / ray_dir_y;
+                (t, origin.0 + t * ray_dir_x, map_x, if step_y > 0 { Face::North } else { Face::South })
+            };
+
+            // El rayo sale de la casilla sin cruzar el plano, o pasa por la parte ya abierta
+            let hit_x = along - along.floor() - maze.door_open(map_x, map_y);
+            if distance > 0.0 && along.floor() as i32 == cell && hit_x >= 0.0 {
+                let hit = RayHit { distance, tile: wall_type, hit_x, face, bottom, top };
+                let done = stop(&hit);
+                hits.push(hit);
+                if done {
+                    break;
+                }
+            }
+            continue;
+        }
+
+        let transparent = maze.tile(map_x, map_y).is_some_and(|tile| tile.transparent);
+        if !transparent && !maze.is_solid(map_x, map_y) {
+            // Piso más alto que el anterior: se ve el frente del escalón
+            let elevation = maze.elevation(map_x, map_y);
+            if elevation > floor_z {
+                let hit = RayHit { distance: distance.abs(), tile: wall_type, hit_x, face, bottom: floor_z, top: elevation };
+                let done = stop(&hit);
+                hits.push(hit);
+                if done {
+                    break;
+                }
+            }
+            floor_z = elevation;
+            continue;
+        }
+
+        let hit = RayHit { distance: distance.abs(), tile: wall_type, hit_x, face, bottom, top };
+        let done = stop(&hit);
+        hits.push(hit);
+        if done {
+            break;
         }
     }
     
-    if !hit {
-        return (f64::INFINITY, 0, 0.0);
-    }
-    
-    let distance = if side == 0 {
-        (map_x as f64 - player.x + (1.0 - step_x as f64) / 2.0) / ray_dir_x
-    } else {
-        (map_y as f64 - player.y + (1.0 - step_y as f64) / 2.0) / ray_dir_y
+    hits
+}
+
+// Intersección del rayo con un bloque de 1x1 cuya esquina es `corner`
+fn box_hit(origin: Point, ray_dir_x: f64, ray_dir_y: f64, corner: (f64, f64), tile: u8, (bottom, top): (f64, f64)) -> Option<RayHit> {
+    // Intervalo de distancias en que el rayo está dentro del bloque en un eje
+    let slab = |start: f64, dir: f64, min: f64| -> (f64, f64) {
+        if dir.abs() < 1e-10 {
+            if start >= min && start <= min + 1.0 {
+                (f64::NEG_INFINITY, f64::INFINITY)
+            } else {
+                (f64::INFINITY, f64::NEG_INFINITY)
+            }
+        } else {
+            let (t0, t1) = ((min - start) / dir, (min + 1.0 - start) / dir);
+            (t0.min(t1), t0.max(t1))
+        }
     };
-    
-    let wall_x = if side == 0 {
-        player.y + distance * ray_dir_y
+
+    let (tx0, tx1) = slab(origin.0, ray_dir_x, corner.0);
+    let (ty0, ty1) = slab(origin.1, ray_dir_y, corner.1);
+    let enter = tx0.max(ty0);
+    if enter <= 0.0 || enter > tx1.min(ty1) {
+        return None;
+    }
+
+    let (hit_x, face) = if tx0 > ty0 {
+        (origin.1 + enter * ray_dir_y - corner.1, if ray_dir_x > 0.0 { Face::West } else { Face::East })
     } else {
-        player.x + distance * ray_dir_x
+        (origin.0 + enter * ray_dir_x - corner.0, if ray_dir_y > 0.0 { Face::North } else { Face::South })
     };
-    
-    let hit_x = wall_x - wall_x.floor();
-    
-    (distance.abs(), wall_type, hit_x)
-}
\ No newline at end of file
+    Some(RayHit { distance: enter, tile, hit_x, face, bottom, top })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::framebuffer::draw_background;
+    use crate::maze::parse_maze;
+
+    const SKY: Color = Color::RGB(10, 20, 30);
+    const FLOOR: Color = Color::RGB(40, 50, 60);
+
+    // Sala vacía de 3x3 rodeada de paredes; sin texturas las caras usan el color del minimapa
+    fn render(framebuffer: &mut Framebuffer, x: f64, angle: f64) -> (u8, u8, u8) {
+        let (maze, errors) = parse_maze("11111\n10001\n10001\n10001\n11111\n", TileSet::builtin());
+        assert!(errors.is_empty());
+        let walls = WallTextures { images: Vec::new(), faces: vec![[None; 4]; 256] };
+        draw_background(framebuffer, SKY, FLOOR);
+        render_scene(framebuffer, &maze, &Player::new(x, 2.5, angle), &walls, 1.0, Environment::default());
+        maze.tiles().get(1).unwrap().minimap
+    }
+
+    #[test]
+    fn walls_fill_the_middle_rows_and_record_their_depth() {
+        let mut framebuffer = Framebuffer::new(9, 8);
+        // Mirando al este desde el centro: la pared x = 4 queda a 1.5 en todas las columnas
+        let wall = render(&mut framebuffer, 2.5, 0.0);
+        for x in 0..9 {
+            assert_eq!(framebuffer.pixel(x, 0), (SKY.r, SKY.g, SKY.b));
+            assert_eq!(framebuffer.pixel(x, 3), wall);
+            assert_eq!(framebuffer.pixel(x, 7), (FLOOR.r, FLOOR.g, FLOOR.b));
+
+            assert!((framebuffer.depth(x as i32, 3) - 1.5).abs() < 1e-9, "{}", framebuffer.depth(x as i32, 3));
+            assert_eq!(framebuffer.depth(x as i32, 0), f64::INFINITY);
+            assert_eq!(framebuffer.depth(x as i32, 7), f64::INFINITY);
+        }
+    }
+
+    #[test]
+    fn depth_is_reset_every_frame() {
+        let mut framebuffer = Framebuffer::new(9, 8);
+        render(&mut framebuffer, 3.5, 0.0);
+        assert!((framebuffer.depth(4, 3) - 0.5).abs() < 1e-9);
+        // Dándose vuelta la pared de enfrente está más lejos: no queda la distancia anterior
+        render(&mut framebuffer, 3.5, std::f64::consts::PI);
+        assert!((framebuffer.depth(4, 3) - 2.5).abs() < 1e-9, "{}", framebuffer.depth(4, 3));
+        assert_eq!(framebuffer.depth(4, 0), f64::INFINITY);
+    }
+}
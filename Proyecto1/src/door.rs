@@ -0,0 +1,137 @@
+// door.rs
+// Puertas corredizas: se abren con la tecla de usar y se cierran solas después de un rato.
+
+const SLIDE_SPEED: f64 = 1.5;    // fracción de la puerta por segundo
+const CLOSE_DELAY: f64 = 3.0;    // segundos abierta antes de cerrarse
+// A partir de cuánto se puede pasar (el jugador no es un punto del todo)
+pub const PASSABLE: f64 = 0.9;
+
+#[derive(Clone, Copy, PartialEq)]
+enum DoorState {
+    Closed,
+    Opening,
+    Open,
+    Closing,
+}
+
+#[derive(Clone)]
+pub struct Door {
+    pub open: f64,   // 0 = cerrada, 1 = abierta
+    state: DoorState,
+    timer: f64,
+}
+
+impl Door {
+    pub fn new() -> Self {
+        Door {
+            open: 0.0,
+            state: DoorState::Closed,
+            timer: 0.0,
+        }
+    }
+
+    // Tecla de usar: abre una puerta cerrada o cerrándose, y cierra una abierta
+    pub fn activate(&mut self) {
+        self.state = match self.state {
+            DoorState::Closed | DoorState::Closing => DoorState::Opening,
+            DoorState::Open | DoorState::Opening => DoorState::Closing,
+        };
+    }
+
+    // `occupied`: hay alguien en la casilla, así que no se puede cerrar
+    pub fn update(&mut self, delta_time: f64, occupied: bool) {
+        match self.state {
+            DoorState::Opening => {
+                self.open = (self.open + SLIDE_SPEED * delta_time).min(1.0);
+                if self.open >= 1.0 {
+                    self.state = DoorState::Open;
+                    self.timer = CLOSE_DELAY;
+                }
+            }
+            DoorState::Open => {
+                self.timer -= delta_time;
+                if self.timer <= 0.0 && !occupied {
+                    self.state = DoorState::Closing;
+                }
+            }
+            DoorState::Closing if occupied => self.state = DoorState::Opening,
+            DoorState::Closing => {
+                self.open = (self.open - SLIDE_SPEED * delta_time).max(0.0);
+                if self.open <= 0.0 {
+                    self.state = DoorState::Closed;
+                }
+            }
+            DoorState::Closed => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maze::parse_maze;
+    use crate::tiles::TileSet;
+
+    // Avanza la puerta `seconds` segundos en pasos de 0.1
+    fn run(door: &mut Door, seconds: f64, occupied: bool) {
+        for _ in 0..(seconds * 10.0).round() as usize {
+            door.update(0.1, occupied);
+        }
+    }
+
+    #[test]
+    fn opens_waits_and_closes() {
+        let mut door = Door::new();
+        door.update(1.0, false);
+        assert_eq!(door.open, 0.0);
+
+        door.activate();
+        run(&mut door, 0.5, false);
+        assert!((door.open - 0.75).abs() < 1e-9);
+        run(&mut door, 0.2, false);
+        assert_eq!(door.open, 1.0);
+
+        // Abierta durante CLOSE_DELAY y después se cierra a la misma velocidad
+        run(&mut door, CLOSE_DELAY - 0.1, false);
+        assert_eq!(door.open, 1.0);
+        run(&mut door, 0.3, false);
+        assert!(door.open < 1.0);
+        run(&mut door, 1.0 / SLIDE_SPEED, false);
+        assert_eq!(door.open, 0.0);
+    }
+
+    #[test]
+    fn does_not_close_on_someone() {
+        let mut door = Door::new();
+        door.activate();
+        run(&mut door, 1.0, false);
+        run(&mut door, CLOSE_DELAY + 1.0, true);
+        assert_eq!(door.open, 1.0);
+
+        // Si alguien entra mientras se cierra, vuelve a abrirse
+        run(&mut door, 0.2, false);
+        let closing = door.open;
+        assert!(closing < 1.0);
+        run(&mut door, 0.2, true);
+        assert!(door.open > closing);
+    }
+
+    #[test]
+    fn blocks_until_passable() {
+        let (mut maze, errors) = parse_maze("11111\n10D01\n11111\n", TileSet::builtin());
+        assert!(errors.is_empty());
+        assert!(maze.blocks(2, 1));
+        assert!(maze.use_door(2, 1));
+        assert!(!maze.use_door(1, 1));
+
+        // Se puede pasar recién cuando llega a PASSABLE, no apenas empieza a abrirse
+        let mut elapsed = 0.0;
+        while maze.blocks(2, 1) {
+            maze.update(0.05, (1, 1));
+            elapsed += 0.05;
+            assert!(elapsed < 1.0, "la puerta no se abrió");
+        }
+        assert!(maze.door_open(2, 1) >= PASSABLE);
+        assert!(maze.door_open(2, 1) - 0.05 * SLIDE_SPEED < PASSABLE);
+    }
+}
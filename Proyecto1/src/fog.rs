@@ -0,0 +1,93 @@
+// fog.rs
+// Niebla por distancia: lo lejano se mezcla con el color de la niebla.
+use sdl2::pixels::Color;
+use serde::Deserialize;
+
+// Qué tan rápido aparece la niebla entre `start` y `end`
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Falloff {
+    #[default]
+    Linear,
+    Exponential,   // crece rápido cerca de `start` y se satura hacia `end`
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Fog {
+    pub color: (u8, u8, u8),
+    pub start: f64,    // antes de esta distancia no hay niebla
+    pub end: f64,      // desde esta distancia solo se ve la niebla
+    pub falloff: Falloff,
+}
+
+impl Fog {
+    pub fn new(color: (u8, u8, u8), start: f64, end: f64, falloff: Falloff) -> Result<Self, String> {
+        if start < 0.0 || end <= start {
+            return Err(format!("niebla: hace falta 0 <= start < end (start = {}, end = {})", start, end));
+        }
+        Ok(Fog { color, start, end, falloff })
+    }
+
+    // Cuánta niebla hay a esta distancia: 0 = nada, 1 = solo niebla
+    pub fn amount(&self, distance: f64) -> f64 {
+        let t = ((distance - self.start) / (self.end - self.start)).clamp(0.0, 1.0);
+        match self.falloff {
+            Falloff::Linear => t,
+            // Normalizada para llegar justo a 1 en `end`
+            Falloff::Exponential => (1.0 - (-3.0 * t).exp()) / (1.0 - (-3.0_f64).exp()),
+        }
+    }
+
+    // El color visto a esta distancia; la transparencia no cambia
+    pub fn apply(&self, color: Color, distance: f64) -> Color {
+        let amount = self.amount(distance);
+        if amount <= 0.0 {
+            return color;
+        }
+        let mix = |c: u8, f: u8| (c as f64 + (f as f64 - c as f64) * amount).round() as u8;
+        let (r, g, b) = self.color;
+        Color::RGBA(mix(color.r, r), mix(color.g, g), mix(color.b, b), color.a)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GRAY: Color = Color::RGBA(100, 100, 100, 200);
+
+    fn fog(falloff: Falloff) -> Fog {
+        Fog::new((200, 0, 50), 2.0, 6.0, falloff).unwrap()
+    }
+
+    fn rgba(color: Color) -> (u8, u8, u8, u8) {
+        (color.r, color.g, color.b, color.a)
+    }
+
+    #[test]
+    fn linear_fog_mixes_by_distance() {
+        let fog = fog(Falloff::Linear);
+        assert_eq!(rgba(fog.apply(GRAY, 0.0)), (100, 100, 100, 200));
+        assert_eq!(rgba(fog.apply(GRAY, 2.0)), (100, 100, 100, 200));
+        assert_eq!(rgba(fog.apply(GRAY, 4.0)), (150, 50, 75, 200));
+        assert_eq!(rgba(fog.apply(GRAY, 6.0)), (200, 0, 50, 200));
+        assert_eq!(rgba(fog.apply(GRAY, 50.0)), (200, 0, 50, 200));
+    }
+
+    #[test]
+    fn exponential_fog_is_thicker_near_the_start() {
+        let fog = fog(Falloff::Exponential);
+        assert_eq!(fog.amount(0.0), 0.0);
+        assert!(fog.amount(4.0) > 0.8 && fog.amount(4.0) < 1.0, "{}", fog.amount(4.0));
+        assert!((fog.amount(6.0) - 1.0).abs() < 1e-12);
+        assert_eq!(rgba(fog.apply(GRAY, 4.0)), (182, 18, 59, 200));
+        assert_eq!(rgba(fog.apply(GRAY, 50.0)), (200, 0, 50, 200));
+    }
+
+    #[test]
+    fn start_must_come_before_end() {
+        assert!(Fog::new((0, 0, 0), 3.0, 3.0, Falloff::Linear).is_err());
+        assert!(Fog::new((0, 0, 0), -1.0, 3.0, Falloff::Linear).is_err());
+        assert!(Fog::new((0, 0, 0), 0.0, 3.0, Falloff::Linear).is_ok());
+    }
+}
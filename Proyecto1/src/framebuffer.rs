@@ -4,70 +4,269 @@ use sdl2::pixels::Color;
 use sdl2::rect::Rect;
 use sdl2::video::WindowContext;
 use sdl2::ttf::Font;
+use std::path::Path;
+use crate::line::line;
 use crate::player::Player;
-use crate::maze;
+use crate::maze::Maze;
+use crate::pathfinding::Cell;
 
-pub fn draw_background(canvas: &mut WindowCanvas, width: u32, height: u32) -> Result<(), String> {
-    // Cielo (mitad superior)
-    canvas.set_draw_color(Color::RGB(135, 206, 235)); // Azul cielo
-    canvas.fill_rect(Rect::new(0, 0, width, height / 2))?;
+// Imagen en memoria (texturas y sprites), para leerla pixel por pixel
+pub struct Image {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>,
+}
+
+impl Image {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let image = image::open(path).map_err(|e| format!("{}: {}", path.display(), e))?.to_rgba8();
+        Ok(Image {
+            width: image.width() as usize,
+            height: image.height() as usize,
+            pixels: image.pixels().map(|p| Color::RGBA(p[0], p[1], p[2], p[3])).collect(),
+        })
+    }
+
+    // Imagen de un solo pixel, para pruebas
+    #[cfg(test)]
+    pub fn filled(color: Color) -> Image {
+        Image { width: 1, height: 1, pixels: vec![color] }
+    }
+
+    // Copia teñida, como el color_mod de una textura de SDL
+    pub fn tinted(&self, (r, g, b): (u8, u8, u8)) -> Image {
+        let tint = |c: u8, t: u8| (c as u16 * t as u16 / 255) as u8;
+        Image {
+            width: self.width,
+            height: self.height,
+            pixels: self.pixels.iter().map(|p| Color::RGBA(tint(p.r, r), tint(p.g, g), tint(p.b, b), p.a)).collect(),
+        }
+    }
+
+    // Color en (u, v), los dos entre 0 y 1
+    pub fn sample(&self, u: f64, v: f64) -> Color {
+        let x = ((u * self.width as f64) as usize).min(self.width - 1);
+        let y = ((v * self.height as f64) as usize).min(self.height - 1);
+        self.pixels[y * self.width + x]
+    }
+}
+
+// Pantalla en memoria. El juego se dibuja acá pixel por pixel y se sube a SDL
+// una sola vez por frame (ver `present`).
+pub struct Framebuffer {
+    pub width: u32,
+    pub height: u32,
+    pixels: Vec<u8>,         // RGB, fila por fila
+    depth: Vec<f64>,         // distancia de la pared opaca más cercana por pixel (ver render_scene)
+    current_color: Color,
+}
+
+impl Framebuffer {
+    pub fn new(width: u32, height: u32) -> Self {
+        Framebuffer {
+            width,
+            height,
+            pixels: vec![0; width as usize * height as usize * 3],
+            depth: vec![f64::INFINITY; width as usize * height as usize],
+            current_color: Color::RGB(255, 255, 255),
+        }
+    }
+
+    pub fn clear(&mut self, color: Color) {
+        for pixel in self.pixels.chunks_exact_mut(3) {
+            pixel.copy_from_slice(&[color.r, color.g, color.b]);
+        }
+    }
+
+    // Olvida las paredes del frame anterior
+    pub fn clear_depth(&mut self) {
+        self.depth.fill(f64::INFINITY);
+    }
+
+    // Distancia de la pared más cercana dibujada en (x, y); infinito si no hay o está fuera
+    pub fn depth(&self, x: i32, y: i32) -> f64 {
+        if x < 0 || y < 0 || x as u32 >= self.width || y as u32 >= self.height {
+            return f64::INFINITY;
+        }
+        self.depth[y as usize * self.width as usize + x as usize]
+    }
+
+    // Anota una pared a `distance` en (x, y) si es la más cercana hasta ahora
+    pub fn mark_depth(&mut self, x: i32, y: i32, distance: f64) {
+        if x < 0 || y < 0 || x as u32 >= self.width || y as u32 >= self.height {
+            return;
+        }
+        let pixel = &mut self.depth[y as usize * self.width as usize + x as usize];
+        *pixel = pixel.min(distance);
+    }
+
+    #[cfg(test)]
+    pub fn pixel(&self, x: u32, y: u32) -> (u8, u8, u8) {
+        let i = (y * self.width + x) as usize * 3;
+        (self.pixels[i], self.pixels[i + 1], self.pixels[i + 2])
+    }
+
+    pub fn set_current_color(&mut self, color: Color) {
+        self.current_color = color;
+    }
+
+    // Pixel con el color actual
+    pub fn set_pixel(&mut self, x: u32, y: u32) {
+        self.draw_pixel(x as i32, y as i32, self.current_color);
+    }
+
+    // Pixel de otro color, mezclado con lo que había según su transparencia.
+    // Lo que cae fuera de la pantalla se ignora.
+    pub fn draw_pixel(&mut self, x: i32, y: i32, color: Color) {
+        if x < 0 || y < 0 || x as u32 >= self.width || y as u32 >= self.height || color.a == 0 {
+            return;
+        }
+        let i = (y as usize * self.width as usize + x as usize) * 3;
+        let pixel = &mut self.pixels[i..i + 3];
+        let alpha = color.a as u16;
+        for (old, new) in pixel.iter_mut().zip([color.r, color.g, color.b]) {
+            *old = ((new as u16 * alpha + *old as u16 * (255 - alpha)) / 255) as u8;
+        }
+    }
+
+    // Parte de `rect` que queda dentro de la pantalla, como rangos de x e y
+    fn clip(&self, rect: Rect) -> (std::ops::Range<i32>, std::ops::Range<i32>) {
+        let x = rect.x().max(0)..(rect.x() + rect.width() as i32).min(self.width as i32);
+        let y = rect.y().max(0)..(rect.y() + rect.height() as i32).min(self.height as i32);
+        (x, y)
+    }
+
+    pub fn fill_rect(&mut self, rect: Rect) {
+        let (xs, ys) = self.clip(rect);
+        for y in ys {
+            for x in xs.clone() {
+                self.draw_pixel(x, y, self.current_color);
+            }
+        }
+    }
+
+    // Solo el borde
+    pub fn draw_rect(&mut self, rect: Rect) {
+        let (left, top) = (rect.x(), rect.y());
+        let (right, bottom) = (rect.right() - 1, rect.bottom() - 1);
+        line(self, (left, top), (right, top));
+        line(self, (left, bottom), (right, bottom));
+        line(self, (left, top + 1), (left, bottom - 1));
+        line(self, (right, top + 1), (right, bottom - 1));
+    }
+
+    // Imagen estirada hasta llenar `dst`, que puede salirse en parte de la pantalla
+    pub fn blit(&mut self, image: &Image, dst: Rect) {
+        let (xs, ys) = self.clip(dst);
+        for y in ys {
+            let v = (y - dst.y()) as f64 / dst.height() as f64;
+            for x in xs.clone() {
+                let u = (x - dst.x()) as f64 / dst.width() as f64;
+                self.draw_pixel(x, y, image.sample(u, v));
+            }
+        }
+    }
+
+    pub fn save_png(&self, path: &Path) -> Result<(), String> {
+        image::save_buffer(path, &self.pixels, self.width, self.height, image::ExtendedColorType::Rgb8)
+            .map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    // Sube el frame a `texture` (streaming, RGB24 del mismo tamaño) y lo copia al canvas
+    pub fn present(&self, canvas: &mut WindowCanvas, texture: &mut Texture) -> Result<(), String> {
+        texture.update(None, &self.pixels, self.width as usize * 3).map_err(|e| e.to_string())?;
+        canvas.copy(texture, None, None)
+    }
+}
+
+pub fn draw_background(framebuffer: &mut Framebuffer, sky: Color, floor: Color) {
+    let (width, height) = (framebuffer.width, framebuffer.height);
+
+    // Cielo (toda la pantalla)
+    framebuffer.clear(sky);
     
     // Piso (mitad inferior)
-    canvas.set_draw_color(Color::RGB(101, 67, 33)); // Marrón
-    canvas.fill_rect(Rect::new(0, (height / 2) as i32, width, height / 2))?;
-    
-    Ok(())
+    framebuffer.set_current_color(floor);
+    framebuffer.fill_rect(Rect::new(0, (height / 2) as i32, width, height - height / 2));
 }
 
-pub fn draw_minimap(canvas: &mut WindowCanvas, player: &Player, screen_width: u32) -> Result<(), String> {
+pub fn draw_minimap(
+    framebuffer: &mut Framebuffer,
+    maze: &Maze,
+    player: Option<&Player>,     // None si se está viendo otro piso
+    hint: Option<&[Cell]>,
+) {
     let minimap_size = 200; // Más grande para ver mejor
-    let cell_size = minimap_size / maze::MAP_WIDTH as i32;
-    let minimap_x = (screen_width as i32 - minimap_size - 10) as i32;
+    // Escala según el lado más largo para que mapas no cuadrados quepan completos
+    let cell_size = minimap_size as f64 / maze.width().max(maze.height()) as f64;
+    let minimap_w = (maze.width() as f64 * cell_size).ceil() as i32;
+    let minimap_h = (maze.height() as f64 * cell_size).ceil() as i32;
+    let minimap_x = framebuffer.width as i32 - minimap_w - 10;
     let minimap_y = 10;
     
     // Fondo del minimapa
-    canvas.set_draw_color(Color::RGBA(0, 0, 0, 200));
-    canvas.fill_rect(Rect::new(minimap_x, minimap_y, minimap_size as u32, minimap_size as u32))?;
+    framebuffer.set_current_color(Color::RGBA(0, 0, 0, 200));
+    framebuffer.fill_rect(Rect::new(minimap_x, minimap_y, minimap_w as u32, minimap_h as u32));
     
     // Dibujar el mapa
-    unsafe {
-        for y in 0..maze::MAP_HEIGHT {
-            for x in 0..maze::MAP_WIDTH {
-                let cell_x = minimap_x + (x as i32 * cell_size);
-                let cell_y = minimap_y + (y as i32 * cell_size);
-                
-                let color = match maze::MAP[y][x] {
-                    0 => Color::RGB(40, 40, 40),     // Vacío (oscuro)
-                    5 => Color::RGB(255, 215, 0),    // Meta (dorado brillante)
-                    _ => Color::RGB(180, 180, 180),  // Pared (gris claro)
-                };
-                
-                canvas.set_draw_color(color);
-                canvas.fill_rect(Rect::new(cell_x, cell_y, cell_size as u32, cell_size as u32))?;
-                
-                // Borde para la meta para que resalte más
-                if maze::MAP[y][x] == 5 {
-                    canvas.set_draw_color(Color::RGB(255, 100, 0)); // Naranja
-                    canvas.draw_rect(Rect::new(cell_x, cell_y, cell_size as u32, cell_size as u32))?;
-                }
+    for y in 0..maze.height() as i32 {
+        for x in 0..maze.width() as i32 {
+            let cell_x = minimap_x + (x as f64 * cell_size) as i32;
+            let cell_y = minimap_y + (y as f64 * cell_size) as i32;
+            let cell_w = (minimap_x + ((x + 1) as f64 * cell_size) as i32 - cell_x).max(1) as u32;
+            let cell_h = (minimap_y + ((y + 1) as f64 * cell_size) as i32 - cell_y).max(1) as u32;
+            
+            // Color de la tabla de casillas; las casillas sin definir, como pared
+            let (r, g, b) = maze.tile(x, y).map(|tile| tile.minimap).unwrap_or((180, 180, 180));
+            
+            framebuffer.set_current_color(Color::RGB(r, g, b));
+            framebuffer.fill_rect(Rect::new(cell_x, cell_y, cell_w, cell_h));
+            
+            // Paredes finas o diagonales
+            framebuffer.set_current_color(Color::RGB(180, 180, 180));
+            for segment in maze.segments(x, y) {
+                let point = |(px, py): (f64, f64)| (minimap_x + (px * cell_size) as i32, minimap_y + (py * cell_size) as i32);
+                line(framebuffer, point(segment.a), point(segment.b));
             }
+            
+            // Borde para la meta para que resalte más
+            if maze.is_exit(x, y) {
+                framebuffer.set_current_color(Color::RGB(255, 100, 0)); // Naranja
+                framebuffer.draw_rect(Rect::new(cell_x, cell_y, cell_w, cell_h));
+            }
+        }
+    }
+    
+    // Pista: camino hasta la salida
+    if let Some(path) = hint {
+        framebuffer.set_current_color(Color::RGB(0, 200, 255));
+        for pair in path.windows(2) {
+            let (ax, ay) = pair[0];
+            let (bx, by) = pair[1];
+            line(
+                framebuffer,
+                (minimap_x + ((ax as f64 + 0.5) * cell_size) as i32, minimap_y + ((ay as f64 + 0.5) * cell_size) as i32),
+                (minimap_x + ((bx as f64 + 0.5) * cell_size) as i32, minimap_y + ((by as f64 + 0.5) * cell_size) as i32),
+            );
         }
     }
     
+    let Some(player) = player else {
+        return;
+    };
+
     // Dibujar jugador (más grande y visible)
-    let player_x = minimap_x + (player.x * cell_size as f64) as i32;
-    let player_y = minimap_y + (player.y * cell_size as f64) as i32;
-    canvas.set_draw_color(Color::RGB(255, 0, 0));
-    canvas.fill_rect(Rect::new(player_x - 3, player_y - 3, 6, 6))?;
+    let player_x = minimap_x + (player.x * cell_size) as i32;
+    let player_y = minimap_y + (player.y * cell_size) as i32;
+    framebuffer.set_current_color(Color::RGB(255, 0, 0));
+    framebuffer.fill_rect(Rect::new(player_x - 3, player_y - 3, 6, 6));
     
     // Dirección del jugador
     let dir_length = 12;
     let end_x = player_x + (player.dir_angle.cos() * dir_length as f64) as i32;
     let end_y = player_y + (player.dir_angle.sin() * dir_length as f64) as i32;
-    canvas.set_draw_color(Color::RGB(255, 255, 0));
-    canvas.draw_line((player_x, player_y), (end_x, end_y))?;
-    
-    Ok(())
+    framebuffer.set_current_color(Color::RGB(255, 255, 0));
+    line(framebuffer, (player_x, player_y), (end_x, end_y));
 }
 
 pub fn draw_fps(
@@ -90,4 +289,201 @@ pub fn draw_fps(
     canvas.copy(&texture, None, Some(target))?;
     
     Ok(())
-}
\ No newline at end of file
+}
+
+// Aviso corto abajo al centro (eventos del nivel, puertas cerradas...)
+pub fn draw_message(
+    canvas: &mut WindowCanvas,
+    font: &Font,
+    texture_creator: &TextureCreator<WindowContext>,
+    text: &str,
+    screen_width: u32,
+    screen_height: u32,
+) -> Result<(), String> {
+    let surface = font
+        .render(text)
+        .blended(Color::RGB(255, 255, 255))
+        .map_err(|e| e.to_string())?;
+    let texture = texture_creator
+        .create_texture_from_surface(&surface)
+        .map_err(|e| e.to_string())?;
+
+    let padding = 10;
+    let (width, height) = (surface.width() + 2 * padding, surface.height() + 2 * padding);
+    let x = (screen_width as i32 - width as i32) / 2;
+    let y = screen_height as i32 - height as i32 - 40;
+    canvas.set_draw_color(Color::RGB(20, 20, 30));
+    canvas.fill_rect(Rect::new(x, y, width, height))?;
+    canvas.set_draw_color(Color::RGB(255, 215, 0));
+    canvas.draw_rect(Rect::new(x, y, width, height))?;
+    let target = Rect::new(x + padding as i32, y + padding as i32, surface.width(), surface.height());
+    canvas.copy(&texture, None, Some(target))?;
+
+    Ok(())
+}
+
+// Pantalla teñida de `color` que se desvanece: `amount` va de 1 (recién empieza) a 0
+pub fn draw_flash(framebuffer: &mut Framebuffer, color: Color, amount: f64) {
+    if amount <= 0.0 {
+        return;
+    }
+    let alpha = (amount.min(1.0) * 220.0) as u8;
+    framebuffer.set_current_color(Color::RGBA(color.r, color.g, color.b, alpha));
+    framebuffer.fill_rect(Rect::new(0, 0, framebuffer.width, framebuffer.height));
+}
+
+// Un cuadrito por piso debajo del minimapa (de abajo hacia arriba, de izquierda a derecha).
+// Relleno el piso del jugador, con borde amarillo el que muestra el minimapa.
+pub fn draw_floor_indicator(
+    framebuffer: &mut Framebuffer,
+    floors: usize,
+    current: usize,
+    viewed: usize,
+) {
+    if floors < 2 {
+        return;
+    }
+    let size = 12;
+    let x0 = framebuffer.width as i32 - 10 - floors as i32 * (size as i32 + 4) + 4;
+    let y = 215;
+    for floor in 0..floors {
+        let rect = Rect::new(x0 + floor as i32 * (size as i32 + 4), y, size, size);
+        framebuffer.set_current_color(if floor == current { Color::RGB(255, 0, 0) } else { Color::RGBA(0, 0, 0, 200) });
+        framebuffer.fill_rect(rect);
+        framebuffer.set_current_color(if floor == viewed { Color::RGB(255, 255, 0) } else { Color::RGB(180, 180, 180) });
+        framebuffer.draw_rect(rect);
+    }
+}
+
+// Íconos de las llaves que lleva el jugador, abajo a la izquierda
+pub fn draw_inventory(framebuffer: &mut Framebuffer, icons: &[&Image]) {
+    let size = 40;
+    let y = framebuffer.height as i32 - size as i32 - 10;
+    for (i, icon) in icons.iter().enumerate() {
+        let x = 10 + i as i32 * (size as i32 + 6);
+        framebuffer.set_current_color(Color::RGBA(0, 0, 0, 200));
+        framebuffer.fill_rect(Rect::new(x, y, size, size));
+        framebuffer.blit(icon, Rect::new(x + 2, y + 2, size - 4, size - 4));
+    }
+}
+
+// Recuadro centrado con líneas de texto (fin de nivel)
+pub fn draw_level_summary(
+    canvas: &mut WindowCanvas,
+    font: &Font,
+    texture_creator: &TextureCreator<WindowContext>,
+    lines: &[String],
+    screen_width: u32,
+    screen_height: u32,
+) -> Result<(), String> {
+    let mut textures = Vec::new();
+    for line in lines {
+        let surface = font
+            .render(line)
+            .blended(Color::RGB(255, 255, 255))
+            .map_err(|e| e.to_string())?;
+        let texture = texture_creator
+            .create_texture_from_surface(&surface)
+            .map_err(|e| e.to_string())?;
+        textures.push((texture, surface.width(), surface.height()));
+    }
+    
+    let padding = 20;
+    let width = textures.iter().map(|t| t.1).max().unwrap_or(0) + 2 * padding;
+    let height = textures.iter().map(|t| t.2).sum::<u32>() + 2 * padding;
+    let x = (screen_width as i32 - width as i32) / 2;
+    let mut y = (screen_height as i32 - height as i32) / 2;
+    
+    canvas.set_draw_color(Color::RGB(20, 20, 30));
+    canvas.fill_rect(Rect::new(x, y, width, height))?;
+    canvas.set_draw_color(Color::RGB(255, 215, 0));
+    canvas.draw_rect(Rect::new(x, y, width, height))?;
+    
+    y += padding as i32;
+    for (texture, w, h) in &textures {
+        let line_x = (screen_width as i32 - *w as i32) / 2;
+        canvas.copy(texture, None, Some(Rect::new(line_x, y, *w, *h)))?;
+        y += *h as i32;
+    }
+    
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Cuántos pixeles no son negros
+    fn painted(framebuffer: &Framebuffer) -> usize {
+        framebuffer.pixels.chunks_exact(3).filter(|p| p != &[0, 0, 0]).count()
+    }
+
+    #[test]
+    fn draw_pixel_ignores_outside_and_blends_alpha() {
+        let mut framebuffer = Framebuffer::new(4, 3);
+        for (x, y) in [(-1, 0), (0, -1), (4, 0), (0, 3), (i32::MAX, i32::MIN)] {
+            framebuffer.draw_pixel(x, y, Color::RGB(255, 255, 255));
+        }
+        assert_eq!(painted(&framebuffer), 0);
+
+        framebuffer.draw_pixel(3, 2, Color::RGB(200, 100, 50));
+        assert_eq!(framebuffer.pixel(3, 2), (200, 100, 50));
+        framebuffer.draw_pixel(3, 2, Color::RGBA(0, 0, 0, 0));
+        assert_eq!(framebuffer.pixel(3, 2), (200, 100, 50));
+        framebuffer.draw_pixel(3, 2, Color::RGBA(0, 0, 0, 255 / 2 + 1));
+        assert_eq!(framebuffer.pixel(3, 2), (99, 49, 24));
+    }
+
+    #[test]
+    fn fill_rect_is_clipped_to_the_screen() {
+        let mut framebuffer = Framebuffer::new(4, 4);
+        framebuffer.set_current_color(Color::RGB(10, 20, 30));
+        framebuffer.fill_rect(Rect::new(-2, 2, 4, 10));
+        assert_eq!(painted(&framebuffer), 4);
+        assert_eq!(framebuffer.pixel(1, 3), (10, 20, 30));
+        assert_eq!(framebuffer.pixel(2, 2), (0, 0, 0));
+
+        framebuffer.fill_rect(Rect::new(10, 10, 5, 5));
+        assert_eq!(painted(&framebuffer), 4);
+    }
+
+    #[test]
+    fn blit_stretches_and_clips() {
+        let image = Image {
+            width: 2,
+            height: 1,
+            pixels: vec![Color::RGB(255, 0, 0), Color::RGBA(0, 255, 0, 0)],
+        };
+        let mut framebuffer = Framebuffer::new(3, 2);
+        // Estirada a 4x2 desde x = -1: la mitad izquierda es roja y la derecha transparente
+        framebuffer.blit(&image, Rect::new(-1, 0, 4, 2));
+        assert_eq!(framebuffer.pixel(0, 0), (255, 0, 0));
+        assert_eq!(framebuffer.pixel(0, 1), (255, 0, 0));
+        assert_eq!(framebuffer.pixel(1, 0), (0, 0, 0));
+        assert_eq!(painted(&framebuffer), 2);
+    }
+
+    #[test]
+    fn line_covers_both_ends_and_clips() {
+        let mut framebuffer = Framebuffer::new(5, 5);
+        line(&mut framebuffer, (0, 0), (4, 2));
+        assert_eq!(framebuffer.pixel(0, 0), (255, 255, 255));
+        assert_eq!(framebuffer.pixel(4, 2), (255, 255, 255));
+        assert_eq!(painted(&framebuffer), 5);
+
+        let mut framebuffer = Framebuffer::new(5, 5);
+        line(&mut framebuffer, (-3, 2), (7, 2));
+        assert_eq!(painted(&framebuffer), 5);
+        line(&mut framebuffer, (-3, -3), (-1, 8));
+        assert_eq!(painted(&framebuffer), 5);
+    }
+
+    #[test]
+    fn draw_rect_only_draws_the_border() {
+        let mut framebuffer = Framebuffer::new(5, 5);
+        framebuffer.draw_rect(Rect::new(0, 0, 4, 3));
+        assert_eq!(painted(&framebuffer), 10);
+        assert_eq!(framebuffer.pixel(1, 1), (0, 0, 0));
+        assert_eq!(framebuffer.pixel(3, 2), (255, 255, 255));
+    }
+}
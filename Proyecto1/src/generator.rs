@@ -0,0 +1,356 @@
+// generator.rs
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use crate::maze::{self, Maze};
+use crate::pathfinding;
+use crate::tiles::TileDef;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Algorithm {
+    Backtracker,
+    Prim,
+    Kruskal,
+    Wilson,
+}
+
+impl Algorithm {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "backtracker" => Some(Algorithm::Backtracker),
+            "prim" => Some(Algorithm::Prim),
+            "kruskal" => Some(Algorithm::Kruskal),
+            "wilson" => Some(Algorithm::Wilson),
+            _ => None,
+        }
+    }
+}
+
+pub struct GeneratorConfig {
+    pub width: usize,
+    pub height: usize,
+    pub seed: u64,
+    pub algorithm: Algorithm,
+    pub loops: f64,      // probabilidad de abrir cada pared interna restante (0 = laberinto perfecto)
+    pub dead_ends: f64,  // fracción de callejones sin salida que se conservan (1 = todos)
+}
+
+impl Default for GeneratorConfig {
+    fn default() -> Self {
+        GeneratorConfig {
+            width: 15,
+            height: 15,
+            seed: 0,
+            algorithm: Algorithm::Backtracker,
+            loops: 0.0,
+            dead_ends: 1.0,
+        }
+    }
+}
+
+// Las celdas del laberinto están en coordenadas impares de la cuadrícula;
+// las casillas pares entre ellas son las paredes que se pueden abrir.
+struct Grid {
+    cols: usize,
+    rows: usize,
+    open: Vec<bool>,   // por casilla del mapa final
+    width: usize,
+}
+
+impl Grid {
+    fn new(width: usize, height: usize) -> Self {
+        Grid {
+            cols: (width - 1) / 2,
+            rows: (height - 1) / 2,
+            open: vec![false; width * height],
+            width,
+        }
+    }
+
+    fn cell_pos(&self, cell: usize) -> (usize, usize) {
+        (2 * (cell % self.cols) + 1, 2 * (cell / self.cols) + 1)
+    }
+
+    fn carve_cell(&mut self, cell: usize) {
+        let (x, y) = self.cell_pos(cell);
+        self.open[y * self.width + x] = true;
+    }
+
+    // Abre la celda b y la pared entre a y b
+    fn carve_between(&mut self, a: usize, b: usize) {
+        let (ax, ay) = self.cell_pos(a);
+        let (bx, by) = self.cell_pos(b);
+        self.open[((ay + by) / 2) * self.width + (ax + bx) / 2] = true;
+        self.carve_cell(a);
+        self.carve_cell(b);
+    }
+
+    fn is_carved_between(&self, a: usize, b: usize) -> bool {
+        let (ax, ay) = self.cell_pos(a);
+        let (bx, by) = self.cell_pos(b);
+        self.open[((ay + by) / 2) * self.width + (ax + bx) / 2]
+    }
+
+    fn neighbors(&self, cell: usize) -> Vec<usize> {
+        let (cx, cy) = (cell % self.cols, cell / self.cols);
+        let mut result = Vec::with_capacity(4);
+        if cx > 0 { result.push(cell - 1); }
+        if cx + 1 < self.cols { result.push(cell + 1); }
+        if cy > 0 { result.push(cell - self.cols); }
+        if cy + 1 < self.rows { result.push(cell + self.cols); }
+        result
+    }
+
+    // Todas las paredes internas entre pares de celdas
+    fn edges(&self) -> Vec<(usize, usize)> {
+        let mut edges = Vec::new();
+        for cell in 0..self.cols * self.rows {
+            for n in self.neighbors(cell) {
+                if n > cell {
+                    edges.push((cell, n));
+                }
+            }
+        }
+        edges
+    }
+}
+
+pub fn generate(config: &GeneratorConfig) -> Maze {
+    // Mínimo 5x5 para tener al menos dos celdas. Los lados tienen que ser impares (celdas y paredes
+    // se alternan): con un lado par quedaría una pared doble al final, así que se achica en uno.
+    let odd = |size: usize| (size - 1) | 1;
+    let width = odd(config.width.max(5));
+    let height = odd(config.height.max(5));
+    let mut rng = StdRng::seed_from_u64(config.seed);
+    let mut grid = Grid::new(width, height);
+
+    match config.algorithm {
+        Algorithm::Backtracker => backtracker(&mut grid, &mut rng),
+        Algorithm::Prim => prim(&mut grid, &mut rng),
+        Algorithm::Kruskal => kruskal(&mut grid, &mut rng),
+        Algorithm::Wilson => wilson(&mut grid, &mut rng),
+    }
+
+    add_loops(&mut grid, &mut rng, config.loops);
+    remove_dead_ends(&mut grid, &mut rng, config.dead_ends);
+
+    let mut maze = Maze::new(width, height);
+
+    let wall_codes: Vec<u8> = maze.tiles().iter().filter(|t| is_plain_wall(t)).map(|t| t.id).collect();
+
+    for y in 0..height {
+        for x in 0..width {
+            let cell = if grid.open[y * width + x] {
+                0
+            } else {
+                *wall_codes.choose(&mut rng).unwrap_or(&1)
+            };
+            maze.set(x, y, cell);
+        }
+    }
+
+    // Salida en la casilla alcanzable más lejana del inicio
+    let (sx, sy) = maze::SPAWN;
+    let distances = pathfinding::distances(&maze, (sx as i32, sy as i32), |_| true);
+    let farthest = distances
+        .iter()
+        .enumerate()
+        .filter_map(|(i, d)| d.map(|d| (i, d)))
+        .max_by_key(|&(_, d)| d)
+        .map(|(i, _)| i)
+        .unwrap_or(sy * width + sx);
+    maze.set(farthest % width, farthest / width, maze::EXIT);
+
+    maze
+}
+
+// Pared común de la tabla: sólida, opaca, con textura, del piso al techo y sin comportamiento
+// especial (ni secreta ni puerta), para no sembrar el laberinto con muros bajos o que se empujan
+fn is_plain_wall(tile: &TileDef) -> bool {
+    tile.solid
+        && !tile.transparent
+        && tile.texture.is_some()
+        && tile.height == 1.0
+        && tile.elevation == 0.0
+        && !tile.secret
+        && !tile.door
+}
+
+fn backtracker(grid: &mut Grid, rng: &mut StdRng) {
+    let total = grid.cols * grid.rows;
+    let mut visited = vec![false; total];
+    let mut stack = vec![0];
+    visited[0] = true;
+    grid.carve_cell(0);
+
+    while let Some(&cell) = stack.last() {
+        let unvisited: Vec<usize> = grid.neighbors(cell).into_iter().filter(|&n| !visited[n]).collect();
+        match unvisited.choose(rng) {
+            Some(&next) => {
+                visited[next] = true;
+                grid.carve_between(cell, next);
+                stack.push(next);
+            }
+            None => {
+                stack.pop();
+            }
+        }
+    }
+}
+
+fn prim(grid: &mut Grid, rng: &mut StdRng) {
+    let total = grid.cols * grid.rows;
+    let mut visited = vec![false; total];
+    let mut frontier: Vec<(usize, usize)> = Vec::new();
+
+    let start = rng.gen_range(0..total);
+    visited[start] = true;
+    grid.carve_cell(start);
+    frontier.extend(grid.neighbors(start).into_iter().map(|n| (start, n)));
+
+    while !frontier.is_empty() {
+        let (from, to) = frontier.swap_remove(rng.gen_range(0..frontier.len()));
+        if visited[to] {
+            continue;
+        }
+        visited[to] = true;
+        grid.carve_between(from, to);
+        frontier.extend(grid.neighbors(to).into_iter().filter(|&n| !visited[n]).map(|n| (to, n)));
+    }
+}
+
+fn kruskal(grid: &mut Grid, rng: &mut StdRng) {
+    let total = grid.cols * grid.rows;
+    let mut parent: Vec<usize> = (0..total).collect();
+
+    fn find(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            parent[i] = parent[parent[i]];
+            i = parent[i];
+        }
+        i
+    }
+
+    let mut edges = grid.edges();
+    edges.shuffle(rng);
+
+    for (a, b) in edges {
+        let (ra, rb) = (find(&mut parent, a), find(&mut parent, b));
+        if ra != rb {
+            parent[ra] = rb;
+            grid.carve_between(a, b);
+        }
+    }
+}
+
+fn wilson(grid: &mut Grid, rng: &mut StdRng) {
+    let total = grid.cols * grid.rows;
+    let mut in_maze = vec![false; total];
+    let first = rng.gen_range(0..total);
+    in_maze[first] = true;
+    grid.carve_cell(first);
+
+    // Caminata aleatoria con borrado de ciclos: `next` guarda la última salida de cada celda
+    let mut next = vec![usize::MAX; total];
+    for start in 0..total {
+        if in_maze[start] {
+            continue;
+        }
+
+        let mut cell = start;
+        while !in_maze[cell] {
+            let step = *grid.neighbors(cell).choose(rng).unwrap();
+            next[cell] = step;
+            cell = step;
+        }
+
+        let mut cell = start;
+        while !in_maze[cell] {
+            in_maze[cell] = true;
+            grid.carve_between(cell, next[cell]);
+            cell = next[cell];
+        }
+    }
+}
+
+fn add_loops(grid: &mut Grid, rng: &mut StdRng, loops: f64) {
+    let loops = loops.clamp(0.0, 1.0);
+    if loops == 0.0 {
+        return;
+    }
+    for (a, b) in grid.edges() {
+        if !grid.is_carved_between(a, b) && rng.gen_bool(loops) {
+            grid.carve_between(a, b);
+        }
+    }
+}
+
+fn remove_dead_ends(grid: &mut Grid, rng: &mut StdRng, dead_ends: f64) {
+    let keep = dead_ends.clamp(0.0, 1.0);
+    if keep == 1.0 {
+        return;
+    }
+    for cell in 0..grid.cols * grid.rows {
+        let neighbors = grid.neighbors(cell);
+        let exits = neighbors.iter().filter(|&&n| grid.is_carved_between(cell, n)).count();
+        if exits != 1 || rng.gen_bool(keep) {
+            continue;
+        }
+        let closed: Vec<usize> = neighbors.into_iter().filter(|&n| !grid.is_carved_between(cell, n)).collect();
+        if let Some(&n) = closed.choose(rng) {
+            grid.carve_between(cell, n);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(seed: u64, algorithm: Algorithm) -> GeneratorConfig {
+        GeneratorConfig { width: 21, height: 15, seed, algorithm, ..GeneratorConfig::default() }
+    }
+
+    #[test]
+    fn same_seed_same_maze() {
+        let first = generate(&GeneratorConfig { loops: 0.2, dead_ends: 0.5, ..config(7, Algorithm::Prim) });
+        let again = generate(&GeneratorConfig { loops: 0.2, dead_ends: 0.5, ..config(7, Algorithm::Prim) });
+        assert_eq!(first.to_text(), again.to_text());
+        assert_ne!(first.to_text(), generate(&config(8, Algorithm::Prim)).to_text());
+    }
+
+    #[test]
+    fn every_algorithm_reaches_its_exit() {
+        for algorithm in [Algorithm::Backtracker, Algorithm::Prim, Algorithm::Kruskal, Algorithm::Wilson] {
+            for seed in 0..3 {
+                let maze = generate(&config(seed, algorithm));
+                assert!(pathfinding::find_exit(&maze).is_some(), "{:?}", algorithm);
+                assert!(maze::validate_maze(&maze, maze::SPAWN).is_empty(), "{:?}", algorithm);
+            }
+        }
+    }
+
+    #[test]
+    fn even_sizes_are_made_odd() {
+        let maze = generate(&GeneratorConfig { width: 16, height: 12, ..GeneratorConfig::default() });
+        assert_eq!((maze.width(), maze.height()), (15, 11));
+        // Sin pared doble: la penúltima columna y fila tienen pasillos
+        assert!((1..10).any(|y| !maze.is_solid(13, y)));
+        assert!((1..14).any(|x| !maze.is_solid(x, 9)));
+    }
+
+    #[test]
+    fn walls_are_plain_and_border_is_closed() {
+        for seed in 0..5 {
+            let maze = generate(&config(seed, Algorithm::Backtracker));
+            assert!(maze::validate_border(&maze).is_empty());
+            for y in 0..maze.height() as i32 {
+                for x in 0..maze.width() as i32 {
+                    let cell = maze.get(x, y).unwrap();
+                    let tile = maze.tiles().get(cell).unwrap();
+                    assert!(cell == 0 || cell == maze::EXIT || is_plain_wall(tile), "casilla {} en ({}, {})", cell, x, y);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,183 @@
+// image_maze.rs
+// Laberintos dibujados en cualquier programa de pintura: cada píxel es una casilla
+// y una paleta dice qué casilla (id de tiles.toml) corresponde a cada color.
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use crate::maze::{self, Maze};
+use crate::tiles::TileSet;
+
+// Cuántos colores desconocidos se listan antes de resumir el resto
+const MAX_REPORTED: usize = 20;
+
+pub struct Palette {
+    colors: HashMap<[u8; 3], u8>,
+}
+
+impl Palette {
+    // Paleta por defecto: blanco = vacío, rojo = salida, el resto paredes
+    pub fn new() -> Self {
+        let colors = HashMap::from([
+            ([255, 255, 255], 0),        // vacío
+            ([0, 0, 0], 1),
+            ([128, 128, 128], 2),
+            ([0, 0, 255], 3),
+            ([0, 255, 0], 4),
+            ([255, 0, 0], maze::EXIT),   // salida
+            ([255, 255, 0], 6),
+            ([255, 0, 255], 7),
+            ([0, 255, 255], 8),
+            ([128, 64, 0], 9),
+            ([255, 128, 0], 10),
+            ([0, 128, 0], 11),
+            ([128, 0, 0], 12),
+        ]);
+        Palette { colors }
+    }
+
+    // Paleta en TOML: `"#rrggbb" = código`, por ejemplo `"#ffffff" = 0`
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let filename = path.display().to_string();
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", filename, e))?;
+        let table: HashMap<String, u8> = toml::from_str(&text).map_err(|e| format!("{}: {}", filename, e))?;
+
+        let mut colors = HashMap::new();
+        for (hex, code) in table {
+            let color = parse_hex(&hex).ok_or_else(|| format!("{}: color inválido {:?}", filename, hex))?;
+            colors.insert(color, code);
+        }
+        Ok(Palette { colors })
+    }
+
+    // Paleta para una imagen: `<nombre>.palette.toml` o `palette.toml` junto a ella, si existen
+    pub fn for_image(image_path: &Path) -> Result<Self, String> {
+        for candidate in palette_paths(image_path) {
+            if candidate.exists() {
+                return Palette::load(&candidate);
+            }
+        }
+        Ok(Palette::new())
+    }
+}
+
+// Archivos de paleta que se buscan para una imagen, en orden de prioridad
+pub fn palette_paths(image_path: &Path) -> Vec<PathBuf> {
+    let dir = image_path.parent().unwrap_or(Path::new("."));
+    let stem = image_path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    vec![dir.join(format!("{}.palette.toml", stem)), dir.join("palette.toml")]
+}
+
+fn parse_hex(hex: &str) -> Option<[u8; 3]> {
+    let hex = hex.strip_prefix('#')?;
+    if hex.len() != 6 {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
+
+// Convierte la imagen en laberinto. Los píxeles transparentes son espacio vacío.
+pub fn load_maze_from_image(path: &Path, palette: &Palette, tiles: Arc<TileSet>) -> Result<Maze, String> {
+    let filename = path.display().to_string();
+    let image = image::open(path).map_err(|e| format!("{}: {}", filename, e))?.to_rgba8();
+    let (width, height) = image.dimensions();
+
+    let mut maze = Maze::with_tiles(width as usize, height as usize, tiles);
+    let mut unknown = Vec::new();
+
+    for (x, y, pixel) in image.enumerate_pixels() {
+        let [r, g, b, a] = pixel.0;
+        if a == 0 {
+            continue;
+        }
+        match palette.colors.get(&[r, g, b]) {
+            Some(&code) if maze.tiles().get(code).is_some() => maze.set(x as usize, y as usize, code),
+            Some(&code) => unknown.push(format!("{}: píxel ({}, {}): la casilla {} no está en la tabla", filename, x, y, code)),
+            None => unknown.push(format!("{}: píxel ({}, {}): color desconocido #{:02x}{:02x}{:02x}", filename, x, y, r, g, b)),
+        }
+    }
+
+    if !unknown.is_empty() {
+        let total = unknown.len();
+        unknown.truncate(MAX_REPORTED);
+        if total > MAX_REPORTED {
+            unknown.push(format!("{}: ... y {} píxeles más con colores desconocidos", filename, total - MAX_REPORTED));
+        }
+        return Err(unknown.join("\n"));
+    }
+
+    Ok(maze)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgba, RgbaImage};
+
+    // Guarda la imagen (y la paleta, si hay) en una carpeta temporal y la carga
+    fn load(name: &str, image: RgbaImage, palette: Option<&str>) -> Result<Maze, String> {
+        let dir = std::env::temp_dir().join(format!("image_maze_test_{}_{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(format!("{}.png", name));
+        image.save(&path).unwrap();
+        if let Some(text) = palette {
+            fs::write(dir.join(format!("{}.palette.toml", name)), text).unwrap();
+        }
+
+        let maze = Palette::for_image(&path).and_then(|palette| load_maze_from_image(&path, &palette, TileSet::builtin()));
+        fs::remove_dir_all(&dir).unwrap();
+        maze
+    }
+
+    fn image(width: u32, height: u32, pixels: &[[u8; 4]]) -> RgbaImage {
+        RgbaImage::from_fn(width, height, |x, y| Rgba(pixels[(y * width + x) as usize]))
+    }
+
+    const BLACK: [u8; 4] = [0, 0, 0, 255];
+    const WHITE: [u8; 4] = [255, 255, 255, 255];
+    const RED: [u8; 4] = [255, 0, 0, 255];
+    const GRAY: [u8; 4] = [128, 128, 128, 255];
+    const BLUE: [u8; 4] = [0, 0, 255, 255];
+    const CLEAR: [u8; 4] = [90, 90, 90, 0];
+
+    #[test]
+    fn default_palette_maps_each_pixel() {
+        let maze = load("defecto", image(3, 3, &[
+            BLACK, GRAY, BLUE,
+            WHITE, CLEAR, RED,
+            BLACK, BLACK, BLACK,
+        ]), None).unwrap();
+
+        assert_eq!((maze.width(), maze.height()), (3, 3));
+        let row = |y| (0..3).map(|x| maze.get(x, y).unwrap()).collect::<Vec<_>>();
+        assert_eq!(row(0), [1, 2, 3]);
+        assert_eq!(row(1), [0, 0, maze::EXIT]);
+        assert_eq!(row(2), [1, 1, 1]);
+    }
+
+    #[test]
+    fn custom_palette_replaces_the_default() {
+        let palette = "\"#102030\" = 4\n\"#ffffff\" = 0\n\"#ff0000\" = 5\n";
+        let maze = load("propia", image(3, 1, &[[16, 32, 48, 255], WHITE, RED]), Some(palette)).unwrap();
+        assert_eq!((maze.get(0, 0), maze.get(1, 0), maze.get(2, 0)), (Some(4), Some(0), Some(maze::EXIT)));
+
+        // El negro ya no es pared: la paleta propia no se mezcla con la de por defecto
+        let error = load("propia", image(1, 1, &[BLACK]), Some(palette)).err().unwrap();
+        assert!(error.ends_with("propia.png: píxel (0, 0): color desconocido #000000"), "{}", error);
+
+        let error = load("propia", image(1, 1, &[BLACK]), Some("\"#000000\" = 200\n")).err().unwrap();
+        assert!(error.ends_with("propia.png: píxel (0, 0): la casilla 200 no está en la tabla"), "{}", error);
+    }
+
+    #[test]
+    fn unknown_colors_are_capped() {
+        let error = load("desconocidos", image(5, 5, &[[18, 52, 86, 255]; 25]), None).err().unwrap();
+        let lines: Vec<&str> = error.lines().collect();
+
+        assert_eq!(lines.len(), MAX_REPORTED + 1);
+        assert!(lines[0].ends_with("desconocidos.png: píxel (0, 0): color desconocido #123456"), "{}", lines[0]);
+        assert!(lines[MAX_REPORTED - 1].ends_with("píxel (4, 3): color desconocido #123456"), "{}", lines[MAX_REPORTED - 1]);
+        assert!(lines[MAX_REPORTED].ends_with("desconocidos.png: ... y 5 píxeles más con colores desconocidos"), "{}", lines[MAX_REPORTED]);
+    }
+}
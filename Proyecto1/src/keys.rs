@@ -0,0 +1,48 @@
+// keys.rs
+// Llaves de colores para las puertas con cerradura, y lo que lleva el jugador.
+
+// Colores de llave que se pueden usar en los niveles y en la tabla de casillas
+pub const KEY_COLORS: [(&str, (u8, u8, u8)); 3] = [
+    ("red", (220, 40, 40)),
+    ("yellow", (240, 200, 40)),
+    ("blue", (50, 90, 230)),
+];
+
+// Posición del color en KEY_COLORS (también el orden de las texturas de llave)
+pub fn key_index(color: &str) -> Option<usize> {
+    KEY_COLORS.iter().position(|(name, _)| *name == color)
+}
+
+pub fn check_key_color(color: &str) -> Result<(), String> {
+    match key_index(color) {
+        Some(_) => Ok(()),
+        None => {
+            let names: Vec<&str> = KEY_COLORS.iter().map(|(name, _)| *name).collect();
+            Err(format!("llave {:?} desconocida; colores: {}", color, names.join(", ")))
+        }
+    }
+}
+
+pub struct Inventory {
+    keys: Vec<String>,   // en el orden en que se recogieron
+}
+
+impl Inventory {
+    pub fn new() -> Self {
+        Inventory { keys: Vec::new() }
+    }
+
+    pub fn add_key(&mut self, color: &str) {
+        if !self.has_key(color) {
+            self.keys.push(color.to_string());
+        }
+    }
+
+    pub fn has_key(&self, color: &str) -> bool {
+        self.keys.iter().any(|k| k == color)
+    }
+
+    pub fn keys(&self) -> &[String] {
+        &self.keys
+    }
+}
@@ -0,0 +1,661 @@
+// level.rs
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use crate::keys;
+use crate::maze::{self, Maze, MazeError};
+use crate::pathfinding::{self, Cell};
+use crate::tiles::{FloorLink, TileEntry, TileSet};
+use crate::tiled;
+use crate::fog::{Falloff, Fog};
+use crate::light::Light;
+use crate::image_maze::{self, Palette};
+
+pub const DEFAULT_SKY: (u8, u8, u8) = (135, 206, 235);   // Azul cielo
+pub const DEFAULT_FLOOR: (u8, u8, u8) = (101, 67, 33);   // Marrón
+
+// Sprite colocado a mano en el nivel. `texture` es el índice en el SpriteRenderer;
+// con `key` es una llave de ese color y la textura se ignora.
+#[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct SpritePlacement {
+    pub x: f64,
+    pub y: f64,
+    #[serde(default)]
+    pub texture: usize,
+    pub key: Option<String>,
+    #[serde(default)]
+    pub floor: usize,
+}
+
+// Una punta de un par de teleportadores
+#[derive(Deserialize, Clone, Copy)]
+#[serde(deny_unknown_fields)]
+pub struct TeleportPad {
+    pub x: i32,
+    pub y: i32,
+    #[serde(default)]
+    pub floor: usize,
+    pub facing: Option<f64>,     // grados al aparecer en esta punta; si falta se mantiene la mirada
+}
+
+// Par de teleportadores: pisar una punta lleva a la otra
+#[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Teleporter {
+    pub a: TeleportPad,
+    pub b: TeleportPad,
+}
+
+impl Teleporter {
+    // La otra punta, si (floor, cell) es una de las dos
+    pub fn partner(&self, floor: usize, (x, y): Cell) -> Option<&TeleportPad> {
+        let at = |pad: &TeleportPad| pad.floor == floor && (pad.x, pad.y) == (x, y);
+        if at(&self.a) {
+            Some(&self.b)
+        } else if at(&self.b) {
+            Some(&self.a)
+        } else {
+            None
+        }
+    }
+}
+
+// Un piso: laberinto en archivo (.txt/.png) o embebido
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FloorFile {
+    maze: Option<String>,
+    grid: Option<String>,
+    palette: Option<String>,
+}
+
+// Formato del archivo .toml tal como lo escribe el autor del nivel
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LevelFile {
+    name: Option<String>,
+    title_card: Option<String>,
+    maze: Option<String>,        // ruta a un .txt o .png
+    grid: Option<String>,        // o el laberinto embebido
+    palette: Option<String>,     // paleta para el .png (ver image_maze)
+    #[serde(default)]
+    floors: Vec<FloorFile>,      // pisos de arriba (1, 2, ...); `maze`/`grid` es el piso 0
+    spawn: Option<[f64; 2]>,
+    spawn_floor: Option<usize>,
+    facing: Option<f64>,         // grados, 0 = mirando hacia +x
+    music: Option<String>,
+    sky_color: Option<[u8; 3]>,
+    floor_color: Option<[u8; 3]>,
+    side_shade: Option<f64>,     // brillo de las caras norte/sur, 0-1
+    fog: Option<FogFile>,
+    ambient: Option<f64>,        // luz donde no llega ninguna de `lights` (1 = normal)
+    #[serde(default)]
+    lights: Vec<LightFile>,
+    #[serde(default)]
+    sprites: Vec<SpritePlacement>,
+    #[serde(default)]
+    teleporters: Vec<Teleporter>,
+    tiles: Option<String>,       // tabla de casillas propia (por defecto assets/tiles.toml)
+    #[serde(default)]
+    tile: Vec<TileEntry>,        // casillas extra o que reemplazan a las de la tabla
+    #[serde(default)]
+    textures: HashMap<String, String>,   // carácter -> textura
+    #[serde(default)]
+    events: HashMap<String, String>,     // `trigger` de una casilla -> aviso al pisarla
+}
+
+// Niebla por distancia, tal como se escribe en el TOML
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FogFile {
+    color: [u8; 3],
+    start: f64,
+    end: f64,
+    #[serde(default)]
+    falloff: Falloff,
+}
+
+// Luz puntual tal como se escribe en el TOML
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LightFile {
+    x: f64,
+    y: f64,
+    #[serde(default)]
+    floor: usize,
+    color: [u8; 3],
+    radius: f64,
+    intensity: Option<f64>,
+    #[serde(default)]
+    flicker: f64,
+}
+
+pub struct Level {
+    pub name: String,
+    pub floors: Vec<Maze>,       // pisos apilados, todos con las mismas x/y
+    pub floor: usize,            // piso en el que está el jugador (empieza en `spawn_floor`)
+    pub title_card: Option<PathBuf>,
+    pub spawn: (f64, f64),
+    pub facing: f64,             // radianes
+    pub music: Option<PathBuf>,
+    pub sky_color: (u8, u8, u8),
+    pub floor_color: (u8, u8, u8),
+    pub side_shade: f64,         // 1.0 = todas las caras igual de iluminadas
+    pub fog: Option<Fog>,
+    pub ambient: f64,
+    pub lights: Vec<Light>,
+    pub sprites: Vec<SpritePlacement>,
+    pub teleporters: Vec<Teleporter>,
+    pub events: HashMap<String, String>,  // aviso en pantalla por cada `trigger` de la tabla de casillas
+    pub sources: Vec<PathBuf>,            // archivos del nivel (el primero es el propio nivel), para recargar en caliente
+}
+
+impl Level {
+    // Nivel con los valores por defecto alrededor de un laberinto
+    pub fn from_maze(name: &str, maze: Maze) -> Self {
+        Level {
+            name: name.to_string(),
+            floors: vec![maze],
+            floor: 0,
+            title_card: None,
+            spawn: (maze::SPAWN.0 as f64 + 0.5, maze::SPAWN.1 as f64 + 0.5),
+            facing: 0.0,
+            music: None,
+            sky_color: DEFAULT_SKY,
+            floor_color: DEFAULT_FLOOR,
+            side_shade: 1.0,
+            fog: None,
+            ambient: 1.0,
+            lights: Vec::new(),
+            sprites: Vec::new(),
+            teleporters: Vec::new(),
+            events: HashMap::new(),
+            sources: Vec::new(),
+        }
+    }
+
+    // Piso actual
+    pub fn maze(&self) -> &Maze {
+        &self.floors[self.floor]
+    }
+
+    pub fn maze_mut(&mut self) -> &mut Maze {
+        &mut self.floors[self.floor]
+    }
+
+    // Piso al que lleva la casilla (x, y) del piso actual
+    pub fn link_target(&self, x: i32, y: i32) -> Option<usize> {
+        link_target(&self.floors, self.floor, (x, y))
+    }
+
+    // Punta de llegada si (x, y) del piso actual es un teleportador
+    pub fn teleport_exit(&self, x: i32, y: i32) -> Option<&TeleportPad> {
+        teleport_exit(&self.teleporters, self.floor, (x, y))
+    }
+
+    // (encontrados, total) de paredes secretas en todos los pisos
+    pub fn secrets(&self) -> (usize, usize) {
+        self.floors.iter().map(|f| f.secrets()).fold((0, 0), |a, b| (a.0 + b.0, a.1 + b.1))
+    }
+}
+
+// Piso al que lleva la casilla `cell` del piso `floor`, si es escalera o ascensor
+pub fn link_target(floors: &[Maze], floor: usize, (x, y): Cell) -> Option<usize> {
+    match floors[floor].floor_link(x, y)? {
+        FloorLink::Up => Some(floor + 1).filter(|&f| f < floors.len()),
+        FloorLink::Down => floor.checked_sub(1),
+        FloorLink::Lift => (1..floors.len())
+            .map(|i| (floor + i) % floors.len())
+            .find(|&f| floors[f].floor_link(x, y) == Some(FloorLink::Lift)),
+    }
+}
+
+pub fn teleport_exit(teleporters: &[Teleporter], floor: usize, cell: Cell) -> Option<&TeleportPad> {
+    teleporters.iter().find_map(|t| t.partner(floor, cell))
+}
+
+// A dónde lleva una casilla: escalera o ascensor (mismo x/y) o teleportador
+fn cell_link(floors: &[Maze], teleporters: &[Teleporter], floor: usize, cell: Cell) -> Option<(usize, Cell)> {
+    link_target(floors, floor, cell)
+        .map(|target| (target, cell))
+        .or_else(|| teleport_exit(teleporters, floor, cell).map(|pad| (pad.floor, (pad.x, pad.y))))
+}
+
+// Validaciones de un nivel con uno o más pisos: todos del mismo tamaño, bordes cerrados, una sola salida en total,
+// escaleras que llevan a casillas libres, teleportadores de a pares y salida alcanzable
+// desde el inicio (subiendo, bajando y teleportando). `names` es el nombre de cada piso para los mensajes.
+fn validate_floors(
+    floors: &[Maze],
+    teleporters: &[Teleporter],
+    names: &[String],
+    filename: &str,
+    spawn_floor: usize,
+    spawn: Cell,
+) -> Result<(), String> {
+    let mut report = Vec::new();
+    let at = |(x, y): Cell| (y as usize + 1, x as usize + 1);
+
+    // Las escaleras y el resto de las validaciones comparan pisos casilla por casilla
+    let size = (floors[0].width(), floors[0].height());
+    for (floor, maze) in floors.iter().enumerate().skip(1) {
+        if (maze.width(), maze.height()) != size {
+            report.push(format!("{}: el piso mide {}x{} y el piso 0 {}x{}; todos los pisos tienen que ser del mismo tamaño",
+                names[floor], maze.width(), maze.height(), size.0, size.1));
+        }
+    }
+    if !report.is_empty() {
+        return Err(report.join("\n"));
+    }
+
+    let mut pads = HashSet::new();
+    for pad in teleporters.iter().flat_map(|t| [&t.a, &t.b]) {
+        let on_tile = floors.get(pad.floor).is_some_and(|maze| maze.is_teleporter(pad.x, pad.y));
+        if !on_tile {
+            report.push(format!("{}: el teleportador en ({}, {}) del piso {} no está sobre una casilla de teleportador",
+                filename, pad.x, pad.y, pad.floor));
+        } else if !pads.insert((pad.floor, pad.x, pad.y)) {
+            report.push(format!("{}: el teleportador en ({}, {}) del piso {} está en más de un par",
+                filename, pad.x, pad.y, pad.floor));
+        }
+    }
+
+    let mut exits = Vec::new();
+    for (floor, maze) in floors.iter().enumerate() {
+        for error in maze::validate_border(maze) {
+            report.push(error.report(&names[floor]));
+        }
+        exits.extend(maze::exit_cells(maze).into_iter().map(|cell| (floor, cell)));
+
+        for y in 0..maze.height() as i32 {
+            for x in 0..maze.width() as i32 {
+                let (line, col) = at((x, y));
+                if maze.is_teleporter(x, y) && !pads.contains(&(floor, x, y)) {
+                    report.push(format!("{}:{}:{}: teleportador sin pareja en `[[teleporters]]`", names[floor], line, col));
+                }
+                if maze.floor_link(x, y).is_none() {
+                    continue;
+                }
+                match link_target(floors, floor, (x, y)) {
+                    None => report.push(format!("{}:{}:{}: la escalera no lleva a ningún piso", names[floor], line, col)),
+                    Some(target) if floors[target].is_solid(x, y) => report.push(format!(
+                        "{}:{}:{}: la escalera lleva a una pared del piso {}", names[floor], line, col, target)),
+                    Some(_) => {}
+                }
+            }
+        }
+    }
+
+    match exits.first() {
+        None => report.push(MazeError::MissingExit.report(filename)),
+        Some(&exit) => {
+            for &(floor, cell) in &exits[1..] {
+                let (line, col) = at(cell);
+                report.push(MazeError::DuplicateExit { line, col }.report(&names[floor]));
+            }
+
+            let (line, col) = at(spawn);
+            if floors[spawn_floor].is_solid(spawn.0, spawn.1) {
+                report.push(MazeError::SpawnInWall { line, col }.report(&names[spawn_floor]));
+            } else {
+                let reachable = pathfinding::reachable_floors(floors, (spawn_floor, spawn), |floor, cell| {
+                    cell_link(floors, teleporters, floor, cell)
+                }, |_, _| false);
+                if !reachable.contains(&exit) {
+                    let (line, col) = at(exit.1);
+                    report.push(MazeError::ExitUnreachable { line, col }.report(&names[exit.0]));
+                }
+            }
+        }
+    }
+
+    if report.is_empty() { Ok(()) } else { Err(report.join("\n")) }
+}
+
+// Lee un piso sin validarlo. Devuelve el laberinto y el nombre para los mensajes de error.
+fn load_floor(
+    floor: &FloorFile,
+    base: &Path,
+    name: &str,
+    tiles: &Arc<TileSet>,
+    sources: &mut Vec<PathBuf>,
+) -> Result<(Maze, String), String> {
+    let resolve = |p: &str| base.join(p);
+    match (&floor.maze, &floor.grid) {
+        (Some(maze_path), None) if is_image(&resolve(maze_path)) => {
+            let maze_path = resolve(maze_path);
+            sources.push(maze_path.clone());
+            let palette = match &floor.palette {
+                Some(palette) => {
+                    sources.push(resolve(palette));
+                    Palette::load(&resolve(palette))?
+                }
+                None => {
+                    sources.extend(image_maze::palette_paths(&maze_path));
+                    Palette::for_image(&maze_path)?
+                }
+            };
+            let maze = image_maze::load_maze_from_image(&maze_path, &palette, tiles.clone())?;
+            Ok((maze, maze_path.display().to_string()))
+        }
+        (Some(maze_path), None) => {
+            let maze_path = resolve(maze_path);
+            sources.push(maze_path.clone());
+            let maze_name = maze_path.display().to_string();
+            let text = fs::read_to_string(&maze_path).map_err(|e| format!("{}: {}", maze_name, e))?;
+            let (maze, errors) = maze::parse_maze(&text, tiles.clone());
+            if !errors.is_empty() {
+                return Err(maze::report_errors(&maze_name, &errors));
+            }
+            Ok((maze, maze_name))
+        }
+        (None, Some(grid)) => {
+            let (maze, errors) = maze::parse_maze(grid, tiles.clone());
+            if !errors.is_empty() {
+                return Err(maze::report_errors(name, &errors));
+            }
+            Ok((maze, name.to_string()))
+        }
+        _ => Err(format!("{}: hay que definir `maze` o `grid` (solo uno)", name)),
+    }
+}
+
+// Laberinto desde una imagen, validado con el inicio en `spawn`
+fn load_image_grid(path: &Path, palette: &Palette, spawn: (usize, usize), tiles: Arc<TileSet>) -> Result<Maze, String> {
+    let maze = image_maze::load_maze_from_image(path, palette, tiles)?;
+    let errors = maze::validate_maze(&maze, spawn);
+    if !errors.is_empty() {
+        return Err(maze::report_errors(&path.display().to_string(), &errors));
+    }
+    Ok(maze)
+}
+
+// Cada llave tiene que ser de un color conocido y estar en una casilla libre, y cada puerta con
+// cerradura necesita su llave en el nivel. Además la llave de cada cerradura y la salida se tienen
+// que poder alcanzar abriendo las puertas solo después de recoger su llave: una llave detrás de
+// su propia puerta, o en un piso al que no se llega, deja el nivel sin terminar.
+pub fn check_keys(level: &Level) -> Result<(), String> {
+    for sprite in &level.sprites {
+        if let Some(color) = &sprite.key {
+            keys::check_key_color(color)?;
+            let free = level.floors.get(sprite.floor).is_some_and(|maze| !maze.is_solid_at(sprite.x, sprite.y));
+            if !free {
+                return Err(format!("la llave {:?} en ({}, {}) del piso {} está fuera del mapa o dentro de una pared",
+                    color, sprite.x, sprite.y, sprite.floor));
+            }
+        }
+    }
+
+    let mut locks = Vec::new();
+    for maze in &level.floors {
+        for y in 0..maze.height() as i32 {
+            for x in 0..maze.width() as i32 {
+                if let Some(lock) = maze.door_lock(x, y) {
+                    if !level.sprites.iter().any(|s| s.key.as_deref() == Some(lock)) {
+                        return Err(format!("la puerta en ({}, {}) necesita la llave {:?} y el nivel no la tiene", x, y, lock));
+                    }
+                    locks.push(((x, y), lock));
+                }
+            }
+        }
+    }
+    if locks.is_empty() {
+        return Ok(());
+    }
+
+    // Se recorre el nivel con las puertas de las llaves que se tienen abiertas, se recogen
+    // las llaves alcanzadas y se repite hasta que no aparece ninguna nueva
+    let start = (level.floor, (level.spawn.0 as i32, level.spawn.1 as i32));
+    let mut held: HashSet<&str> = HashSet::new();
+    let reachable = loop {
+        let reachable = pathfinding::reachable_floors(&level.floors, start, |floor, cell| {
+            cell_link(&level.floors, &level.teleporters, floor, cell)
+        }, |floor, (x, y)| {
+            level.floors[floor].door_lock(x, y).is_some_and(|lock| !held.contains(lock))
+        });
+        let found: Vec<&str> = level.sprites.iter()
+            .filter(|s| reachable.contains(&(s.floor, (s.x as i32, s.y as i32))))
+            .filter_map(|s| s.key.as_deref())
+            .filter(|key| !held.contains(key))
+            .collect();
+        if found.is_empty() {
+            break reachable;
+        }
+        held.extend(found);
+    };
+
+    if let Some(((x, y), lock)) = locks.iter().find(|(_, lock)| !held.contains(lock)) {
+        return Err(format!("la puerta en ({}, {}) necesita la llave {:?} y no se puede llegar a ninguna sin pasar antes por una puerta cerrada",
+            x, y, lock));
+    }
+    let exits = level.floors.iter().enumerate()
+        .flat_map(|(floor, maze)| maze::exit_cells(maze).into_iter().map(move |cell| (floor, cell)));
+    if let Some((floor, (x, y))) = exits.into_iter().find(|exit| !reachable.contains(exit)) {
+        return Err(format!("la salida en ({}, {}) del piso {} no se alcanza abriendo las puertas con las llaves del nivel", x, y, floor));
+    }
+    Ok(())
+}
+
+fn is_image(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "png")
+}
+
+// Si alguna casilla del piso dispara el evento
+fn has_trigger(maze: &Maze, event: &str) -> bool {
+    (0..maze.height() as i32).any(|y| (0..maze.width() as i32).any(|x| maze.trigger(x, y) == Some(event)))
+}
+
+// Carga un nivel .toml, un mapa de Tiled (.tmx/.tmj), una imagen .png
+// o un .txt como formato antiguo (solo el laberinto)
+pub fn load_level(path: &Path) -> Result<Level, String> {
+    let filename = path.display().to_string();
+    let name = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("txt") => {
+            let maze = maze::load_maze_from_file(&filename)?;
+            let mut level = Level::from_maze(&name, maze);
+            level.sources.push(path.to_path_buf());
+            check_keys(&level).map_err(|e| format!("{}: {}", filename, e))?;
+            return Ok(level);
+        }
+        Some("tmx") | Some("tmj") => return tiled::load_tiled(path),
+        Some("png") => {
+            let maze = load_image_grid(path, &Palette::for_image(path)?, maze::SPAWN, TileSet::builtin())?;
+            let mut level = Level::from_maze(&name, maze);
+            level.sources.push(path.to_path_buf());
+            level.sources.extend(image_maze::palette_paths(path));
+            check_keys(&level).map_err(|e| format!("{}: {}", filename, e))?;
+            return Ok(level);
+        }
+        _ => {}
+    }
+
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", filename, e))?;
+    let file: LevelFile = toml::from_str(&text).map_err(|e| format!("{}: {}", filename, e))?;
+
+    // Las rutas del nivel son relativas a la carpeta del archivo
+    let base = path.parent().unwrap_or(Path::new("."));
+    let resolve = |p: &str| base.join(p);
+
+    let spawn = file.spawn.map(|[x, y]| (x, y))
+        .unwrap_or((maze::SPAWN.0 as f64 + 0.5, maze::SPAWN.1 as f64 + 0.5));
+    if spawn.0 < 0.0 || spawn.1 < 0.0 {
+        return Err(format!("{}: spawn fuera del mapa", filename));
+    }
+    let spawn_cell = (spawn.0 as usize, spawn.1 as usize);
+
+    let mut sources = vec![path.to_path_buf()];
+
+    // Tabla de casillas: la del nivel o la por defecto, más las casillas y texturas propias
+    let mut tiles = match &file.tiles {
+        Some(tiles_path) => {
+            sources.push(resolve(tiles_path));
+            TileSet::load(&resolve(tiles_path))?
+        }
+        None => TileSet::builtin().as_ref().clone(),
+    };
+    tiles.merge(&file.tile, base).map_err(|e| format!("{}: {}", filename, e))?;
+    for (glyph, texture) in &file.textures {
+        let mut chars = glyph.chars();
+        let id = match (chars.next(), chars.next()) {
+            (Some(ch), None) => tiles.id_for(ch),
+            _ => None,
+        };
+        match id {
+            Some(id) => tiles.set_texture(id, resolve(texture)),
+            None => return Err(format!("{}: textura para {:?}, que no es una casilla de la tabla", filename, glyph)),
+        }
+    }
+    let tiles = Arc::new(tiles);
+
+    // Piso 0 (`maze`/`grid`) y los de `[[floors]]`
+    let ground = FloorFile { maze: file.maze, grid: file.grid, palette: file.palette };
+    let mut floors = Vec::new();
+    let mut names = Vec::new();
+    for (i, floor) in std::iter::once(&ground).chain(&file.floors).enumerate() {
+        let name = if i == 0 { filename.clone() } else { format!("{} (piso {})", filename, i) };
+        let (maze, name) = load_floor(floor, base, &name, &tiles, &mut sources)?;
+        floors.push(maze);
+        names.push(name);
+    }
+
+    let spawn_floor = file.spawn_floor.unwrap_or(0);
+    if spawn_floor >= floors.len() {
+        return Err(format!("{}: spawn_floor {} no existe (hay {} pisos)", filename, spawn_floor, floors.len()));
+    }
+    if let Some(sprite) = file.sprites.iter().find(|s| s.floor >= floors.len()) {
+        return Err(format!("{}: sprite en ({}, {}) en el piso {}, que no existe", filename, sprite.x, sprite.y, sprite.floor));
+    }
+    validate_floors(&floors, &file.teleporters, &names, &filename, spawn_floor, (spawn_cell.0 as i32, spawn_cell.1 as i32))?;
+    let fog = match &file.fog {
+        Some(fog) => {
+            let [r, g, b] = fog.color;
+            Some(Fog::new((r, g, b), fog.start, fog.end, fog.falloff).map_err(|e| format!("{}: {}", filename, e))?)
+        }
+        None => None,
+    };
+    let ambient = file.ambient.unwrap_or(1.0);
+    if ambient < 0.0 {
+        return Err(format!("{}: `ambient` no puede ser negativa", filename));
+    }
+    let mut lights = Vec::new();
+    for light in &file.lights {
+        let [r, g, b] = light.color;
+        let light = Light {
+            x: light.x,
+            y: light.y,
+            floor: light.floor,
+            color: (r, g, b),
+            radius: light.radius,
+            intensity: light.intensity.unwrap_or(1.0),
+            flicker: light.flicker,
+        };
+        light.check().map_err(|e| format!("{}: {}", filename, e))?;
+        let inside = floors.get(light.floor).is_some_and(|maze| !maze.is_solid_at(light.x, light.y));
+        if !inside {
+            return Err(format!("{}: luz en ({}, {}) del piso {} fuera del mapa o dentro de una pared", filename, light.x, light.y, light.floor));
+        }
+        lights.push(light);
+    }
+
+    // Un evento que ninguna casilla dispara es casi seguro un error de tipeo
+    if let Some(event) = file.events.keys().find(|e| !floors.iter().any(|maze| has_trigger(maze, e))) {
+        return Err(format!("{}: el evento {:?} no lo dispara ninguna casilla del nivel", filename, event));
+    }
+
+    let level = Level {
+        name: file.name.unwrap_or(name),
+        floors,
+        floor: spawn_floor,
+        title_card: file.title_card.as_deref().map(resolve),
+        spawn,
+        facing: file.facing.unwrap_or(0.0).to_radians(),
+        music: file.music.as_deref().map(resolve),
+        sky_color: file.sky_color.map(|[r, g, b]| (r, g, b)).unwrap_or(DEFAULT_SKY),
+        floor_color: file.floor_color.map(|[r, g, b]| (r, g, b)).unwrap_or(DEFAULT_FLOOR),
+        side_shade: file.side_shade.unwrap_or(1.0),
+        fog,
+        ambient,
+        lights,
+        sprites: file.sprites,
+        teleporters: file.teleporters,
+        events: file.events,
+        sources,
+    };
+    check_keys(&level).map_err(|e| format!("{}: {}", filename, e))?;
+    Ok(level)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Nivel de un pasillo (spawn en la casilla 1, 1) con llaves en las posiciones dadas
+    fn corridor(text: &str, keys: &[(f64, &str)]) -> Level {
+        let (maze, errors) = maze::parse_maze(text, TileSet::builtin());
+        assert!(errors.is_empty());
+        let mut level = Level::from_maze("t", maze);
+        for &(x, color) in keys {
+            level.sprites.push(SpritePlacement { x, y: 1.5, texture: 0, key: Some(color.to_string()), floor: 0 });
+        }
+        level
+    }
+
+    #[test]
+    fn key_behind_its_own_door_is_rejected() {
+        let level = corridor("11111111\n10r00051\n11111111\n", &[(3.5, "red")]);
+        assert_eq!(check_keys(&level).err().as_deref(), Some(
+            "la puerta en (2, 1) necesita la llave \"red\" y no se puede llegar a ninguna sin pasar antes por una puerta cerrada"
+        ));
+
+        let level = corridor("11111111\n10r00051\n11111111\n", &[]);
+        assert_eq!(check_keys(&level).err().as_deref(), Some("la puerta en (2, 1) necesita la llave \"red\" y el nivel no la tiene"));
+    }
+
+    #[test]
+    fn reachable_keys_open_their_doors() {
+        let level = corridor("11111111\n100r0051\n11111111\n", &[(2.5, "red")]);
+        assert_eq!(check_keys(&level), Ok(()));
+
+        // La llave amarilla está detrás de la puerta roja y abre la siguiente
+        let level = corridor("1111111111\n100r00y051\n1111111111\n", &[(5.5, "yellow"), (1.5, "red")]);
+        assert_eq!(check_keys(&level), Ok(()));
+    }
+
+    #[test]
+    fn keys_must_be_on_open_floor() {
+        let level = corridor("11111111\n100r0051\n11111111\n", &[(7.5, "red")]);
+        let error = check_keys(&level).err().unwrap();
+        assert!(error.starts_with("la llave \"red\" en (7.5, 1.5) del piso 0 está fuera del mapa o dentro de una pared"), "{}", error);
+    }
+
+    // Escribe un nivel de una sola sala con las líneas extra dadas y lo carga
+    fn load_toml(name: &str, extra: &str) -> Result<Level, String> {
+        let dir = std::env::temp_dir().join(format!("level_test_{}_{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("sala.txt"), "11111\n10051\n11111\n").unwrap();
+        fs::write(dir.join("sala.toml"), format!("maze = \"sala.txt\"\n{}\n", extra)).unwrap();
+        let level = load_level(&dir.join("sala.toml"));
+        fs::remove_dir_all(&dir).unwrap();
+        level
+    }
+
+    #[test]
+    fn parses_fog_from_the_level_toml() {
+        let level = load_toml("niebla", "fog = { color = [15, 10, 25], start = 1.5, end = 9.0, falloff = \"exponential\" }").unwrap();
+        let fog = level.fog.unwrap();
+        assert_eq!((fog.color, fog.start, fog.end, fog.falloff), ((15, 10, 25), 1.5, 9.0, Falloff::Exponential));
+
+        // Sin `falloff` es lineal, y sin tabla no hay niebla
+        let fog = load_toml("lineal", "[fog]\ncolor = [0, 0, 0]\nstart = 0.0\nend = 4.0").unwrap().fog.unwrap();
+        assert_eq!(fog.falloff, Falloff::Linear);
+        assert!(load_toml("sin", "").unwrap().fog.is_none());
+
+        let error = load_toml("mala", "fog = { color = [0, 0, 0], start = 5.0, end = 2.0 }").err().unwrap();
+        assert!(error.ends_with("sala.toml: niebla: hace falta 0 <= start < end (start = 5, end = 2)"), "{}", error);
+        assert!(load_toml("campo", "fog = { color = [0, 0, 0], start = 0.0, end = 2.0, density = 1.0 }").is_err());
+    }
+}
@@ -0,0 +1,176 @@
+// light.rs
+// Luces puntuales (antorchas, carteles de neón) horneadas en un mapa de luz por casilla.
+// Las paredes que hay entre una luz y una casilla se buscan con los rayos del caster.
+use sdl2::pixels::Color;
+use crate::caster::line_of_sight;
+use crate::maze::Maze;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Light {
+    pub x: f64,
+    pub y: f64,
+    pub floor: usize,
+    pub color: (u8, u8, u8),
+    pub radius: f64,       // hasta dónde llega (en casillas)
+    pub intensity: f64,    // 1 = suma su color entero en el centro
+    pub flicker: f64,      // 0 = fija, 1 = titila hasta casi apagarse
+}
+
+impl Light {
+    pub fn check(&self) -> Result<(), String> {
+        let at = format!("luz en ({}, {})", self.x, self.y);
+        if self.radius <= 0.0 || self.intensity < 0.0 {
+            return Err(format!("{}: `radius` tiene que ser mayor que 0 e `intensity` no puede ser negativa", at));
+        }
+        if !(0.0..=1.0).contains(&self.flicker) {
+            return Err(format!("{}: `flicker` va de 0 a 1", at));
+        }
+        Ok(())
+    }
+
+    // Brillo en el momento `time` (segundos): 1 para una luz fija. `seed` hace que
+    // las luces no titilen todas a la vez.
+    fn brightness(&self, time: f64, seed: usize) -> f64 {
+        if self.flicker <= 0.0 {
+            return 1.0;
+        }
+        let phase = seed as f64 * 1.7;
+        let wave = (time * 11.0 + phase).sin() * (time * 7.3 + phase * 2.3).sin();
+        1.0 - self.flicker * 0.5 * (1.0 + wave)
+    }
+}
+
+// Luz de cada casilla de un piso. Lo que no alcanza ninguna luz queda con `ambient`.
+pub struct LightMap {
+    width: usize,
+    height: usize,
+    ambient: f64,
+    lights: Vec<Light>,
+    reach: Vec<Vec<(usize, f64)>>,   // por casilla: luces que llegan y con qué fuerza (0..1)
+    levels: Vec<[f64; 3]>,           // luz actual por casilla y canal (ver update)
+}
+
+impl LightMap {
+    // Qué luces llegan a cada casilla: dentro del radio y sin una pared entera en el medio.
+    // Las puertas cuentan como estaban al hornear (cerradas).
+    pub fn bake(maze: &Maze, lights: &[Light], ambient: f64) -> Self {
+        let (width, height) = (maze.width(), maze.height());
+        let mut reach = vec![Vec::new(); width * height];
+        for (i, light) in lights.iter().enumerate() {
+            for y in 0..height {
+                for x in 0..width {
+                    if maze.is_solid(x as i32, y as i32) {
+                        continue;
+                    }
+                    let (dx, dy) = (x as f64 + 0.5 - light.x, y as f64 + 0.5 - light.y);
+                    let distance = (dx * dx + dy * dy).sqrt();
+                    if distance >= light.radius {
+                        continue;
+                    }
+                    // Alcanza con llegar al lado de la casilla que mira a la luz
+                    let near = ((distance - 0.49) / distance).max(0.0);
+                    if line_of_sight(maze, (light.x, light.y), (light.x + dx * near, light.y + dy * near)) {
+                        reach[y * width + x].push((i, 1.0 - distance / light.radius));
+                    }
+                }
+            }
+        }
+
+        let mut map = LightMap {
+            width,
+            height,
+            ambient,
+            lights: lights.to_vec(),
+            reach,
+            levels: vec![[ambient; 3]; width * height],
+        };
+        map.update(0.0);
+        map
+    }
+
+    // Recalcula la luz de las casillas con el titileo del momento `time` (segundos)
+    pub fn update(&mut self, time: f64) {
+        let current: Vec<[f64; 3]> = self.lights.iter()
+            .enumerate()
+            .map(|(i, light)| {
+                let strength = light.intensity * light.brightness(time, i) / 255.0;
+                let (r, g, b) = light.color;
+                [r as f64 * strength, g as f64 * strength, b as f64 * strength]
+            })
+            .collect();
+
+        for (level, reach) in self.levels.iter_mut().zip(&self.reach) {
+            *level = [self.ambient; 3];
+            for &(i, weight) in reach {
+                for (channel, light) in level.iter_mut().zip(current[i]) {
+                    *channel += light * weight;
+                }
+            }
+        }
+    }
+
+    // Color de algo que está en (x, y), con la luz de su casilla
+    pub fn apply(&self, color: Color, x: f64, y: f64) -> Color {
+        let inside = x >= 0.0 && y >= 0.0 && (x as usize) < self.width && (y as usize) < self.height;
+        let [r, g, b] = if inside {
+            self.levels[y as usize * self.width + x as usize]
+        } else {
+            [self.ambient; 3]
+        };
+        let light = |c: u8, level: f64| (c as f64 * level).min(255.0) as u8;
+        Color::RGBA(light(color.r, r), light(color.g, g), light(color.b, b), color.a)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maze::parse_maze;
+    use crate::tiles::TileSet;
+
+    // Pasillo de dos filas con una pared en (5, 1)
+    fn maze() -> Maze {
+        let (maze, errors) = parse_maze("111111111\n100001001\n100000001\n111111111\n", TileSet::builtin());
+        assert!(errors.is_empty());
+        maze
+    }
+
+    // Luz blanca fija en (1.5, 1.5) que llega a 6 casillas
+    fn light_map() -> LightMap {
+        let light = Light { x: 1.5, y: 1.5, floor: 0, color: (255, 255, 255), radius: 6.0, intensity: 1.0, flicker: 0.0 };
+        LightMap::bake(&maze(), &[light], 0.2)
+    }
+
+    // Cómo queda un gris 100 en el centro de la casilla
+    fn lit(map: &LightMap, x: usize, y: usize) -> u8 {
+        map.apply(Color::RGB(100, 100, 100), x as f64 + 0.5, y as f64 + 0.5).r
+    }
+
+    #[test]
+    fn light_fades_with_distance() {
+        let map = light_map();
+        assert_eq!(lit(&map, 1, 1), 120);   // ambiente + la luz entera
+        assert_eq!(lit(&map, 2, 1), 103);
+        assert_eq!(lit(&map, 3, 1), 86);
+        assert_eq!(lit(&map, 4, 2), 67);
+        // Fuera del radio y fuera del mapa solo queda el ambiente
+        assert_eq!(lit(&map, 7, 2), 20);
+        assert_eq!(map.apply(Color::RGB(100, 100, 100), -3.0, 1.5).r, 20);
+    }
+
+    #[test]
+    fn walls_block_the_light() {
+        let map = light_map();
+        // Detrás de la pared (5, 1) no llega, pero a la casilla de abajo se ve por al lado
+        assert!(!line_of_sight(&maze(), (1.5, 1.5), (6.01, 1.5)));
+        assert_eq!(lit(&map, 6, 1), 20);
+        assert_eq!(lit(&map, 6, 2), 35);
+    }
+
+    #[test]
+    fn steady_lights_do_not_change() {
+        let mut map = light_map();
+        map.update(3.7);
+        assert_eq!(lit(&map, 3, 1), 86);
+    }
+}
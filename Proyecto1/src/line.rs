@@ -1,17 +1,15 @@
 // line.rs
+// Línea de Bresenham con el color actual del framebuffer
 
-use raylib::prelude::*;
 use crate::framebuffer::Framebuffer;
 
 pub fn line(
     framebuffer: &mut Framebuffer,
-    start: Vector2,
-    end: Vector2,
+    start: (i32, i32),
+    end: (i32, i32),
 ) {
-    let mut x0 = start.x as i32;
-    let mut y0 = start.y as i32;
-    let x1 = end.x as i32;
-    let y1 = end.y as i32;
+    let (mut x0, mut y0) = start;
+    let (x1, y1) = end;
 
     let dx = (x1 - x0).abs();
     let dy = -(y1 - y0).abs();
@@ -41,4 +39,4 @@ pub fn line(
             y0 += sy;
         }
     }
-}
\ No newline at end of file
+}
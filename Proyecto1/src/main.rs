@@ -2,24 +2,337 @@ mod maze;
 mod player;
 mod caster;
 mod framebuffer;
+mod line;
 mod sprite;
+mod level;
+mod generator;
+mod pathfinding;
+mod pack;
+mod tiled;
+mod image_maze;
+mod watch;
+mod tiles;
+mod door;
+mod keys;
+mod pushwall;
+mod segment;
+mod fog;
+mod light;
 
 use sdl2::event::Event;
 use sdl2::keyboard::Keycode;
 use sdl2::image::LoadTexture;
 use sdl2::rect::Rect;
-use std::time::{Duration, Instant};
+use sdl2::pixels::{Color, PixelFormatEnum};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use sprite::{Sprite, SpriteRenderer, is_empty_cell};
 
-use crate::maze::load_maze_from_file;
+use crate::maze::{check_maze_file, save_maze_to_file};
+use crate::level::{load_level, Level};
+use crate::generator::{generate, Algorithm, GeneratorConfig};
+use crate::pathfinding::{astar_path, bfs_path_linked, distance_to_exit, find_exit, nearest_open_cell, reachable};
+use crate::watch::FileWatcher;
+use crate::pack::{discover_packs, LevelPack};
+use rand::seq::SliceRandom;
 use crate::player::Player;
-use crate::caster::render_scene;
-use crate::framebuffer::{draw_background, draw_minimap, draw_fps};
+use crate::caster::{render_planes, render_scene, Environment, FloorTextures, View, WallTextures};
+use crate::framebuffer::{draw_background, draw_minimap, draw_floor_indicator, draw_flash, draw_fps, draw_inventory, draw_level_summary, draw_message, Framebuffer, Image};
+use crate::keys::KEY_COLORS;
+use crate::light::{Light, LightMap};
 
 const SCREEN_WIDTH: u32 = 800;
 const SCREEN_HEIGHT: u32 = 600;
+const DEFAULT_MUSIC: &str = "../assets/music/taylor.mp3";
+const MAZE_DIR: &str = "../maze";
+// Las texturas de llave van después del álbum (0) y hs (1), en el orden de KEY_COLORS
+const FIRST_KEY_TEXTURE: usize = 2;
+// Duración del destello al teleportarse (segundos)
+const TELEPORT_FLASH: f64 = 0.4;
+// Cuánto se ve un aviso en pantalla (segundos)
+const MESSAGE_TIME: f64 = 2.5;
+const FOV: f64 = std::f64::consts::PI / 3.0;
+
+// --check-maze <archivo>...: valida laberintos sin abrir la ventana
+fn check_mazes(files: &[String]) -> Result<(), String> {
+    if files.is_empty() {
+        return Err("uso: --check-maze <archivo>...".to_string());
+    }
+
+    let mut total_errors = 0;
+    for file in files {
+        // Los niveles (.toml, Tiled) se validan completos, incluido el laberinto que referencian
+        if !file.ends_with(".txt") {
+            match load_level(Path::new(file)) {
+                Ok(_) => println!("{}: OK", file),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    total_errors += 1;
+                }
+            }
+            continue;
+        }
+
+        let (maze, errors) = check_maze_file(file)?;
+        for error in &errors {
+            eprintln!("{}", error.report(file));
+        }
+        if errors.is_empty() {
+            let spawn = (maze::SPAWN.0 as i32, maze::SPAWN.1 as i32);
+            let steps = distance_to_exit(&maze, spawn).unwrap_or(0);
+            println!("{}: OK, salida a {} pasos del inicio", file, steps);
+        }
+        total_errors += errors.len();
+    }
+
+    if total_errors > 0 {
+        eprintln!("{} error(es) encontrados", total_errors);
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+// Semilla a partir del reloj para niveles aleatorios
+fn clock_seed() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos() as u64).unwrap_or(0)
+}
+
+// --generate-maze <salida.txt> [--seed N] [--size AxB] [--algorithm nombre] [--loops p] [--dead-ends p]
+fn generate_maze_command(args: &[String]) -> Result<(), String> {
+    let usage = "uso: --generate-maze <salida.txt> [--seed N] [--size AxB] \
+                 [--algorithm backtracker|prim|kruskal|wilson] [--loops p] [--dead-ends p]";
+    let out = args.first().ok_or(usage)?;
+    let mut config = GeneratorConfig { seed: clock_seed(), ..GeneratorConfig::default() };
+
+    let mut options = args[1..].iter();
+    while let Some(flag) = options.next() {
+        let value = options.next().ok_or_else(|| format!("falta el valor de {}\n{}", flag, usage))?;
+        let bad_value = || format!("valor inválido para {}: {}", flag, value);
+        match flag.as_str() {
+            "--seed" => config.seed = value.parse().map_err(|_| bad_value())?,
+            "--size" => {
+                let (w, h) = value.split_once('x').ok_or_else(bad_value)?;
+                config.width = w.parse().map_err(|_| bad_value())?;
+                config.height = h.parse().map_err(|_| bad_value())?;
+            }
+            "--algorithm" => config.algorithm = Algorithm::from_name(value).ok_or_else(bad_value)?,
+            "--loops" => config.loops = value.parse().map_err(|_| bad_value())?,
+            "--dead-ends" => config.dead_ends = value.parse().map_err(|_| bad_value())?,
+            _ => return Err(format!("opción desconocida: {}\n{}", flag, usage)),
+        }
+    }
+
+    let maze = generate(&config);
+    save_maze_to_file(&maze, out)?;
+    println!("{}: {}x{}, {:?}, semilla {}", out, maze.width(), maze.height(), config.algorithm, config.seed);
+    Ok(())
+}
+
+// Índice en el SpriteRenderer de la textura de llave de un color (van después del álbum y hs)
+fn key_texture(color: &str) -> usize {
+    FIRST_KEY_TEXTURE + keys::key_index(color).unwrap_or(0)
+}
+
+// Álbum, hs y una llave teñida por cada color, en ese orden (ver key_texture)
+fn load_sprite_textures() -> Result<SpriteRenderer, String> {
+    let mut sprite_renderer = SpriteRenderer::new();
+    sprite_renderer.add_texture(Image::load(Path::new("../assets/sprites/album.png"))?);
+    sprite_renderer.add_texture(Image::load(Path::new("../assets/sprites/hs.png"))?);
+    let key_image = Image::load(Path::new("../assets/sprites/key.png"))?;
+    for (_, color) in KEY_COLORS {
+        sprite_renderer.add_texture(key_image.tinted(color));
+    }
+    Ok(sprite_renderer)
+}
+
+// Sprites que define el nivel
+fn add_level_sprites(sprite_renderer: &mut SpriteRenderer, level: &Level) {
+    for placement in &level.sprites {
+        let texture_index = match &placement.key {
+            Some(color) => key_texture(color),
+            None => placement.texture,
+        };
+        sprite_renderer.add_sprite(Sprite {
+            x: placement.x,
+            y: placement.y,
+            texture_index,
+            key: placement.key.clone(),
+            floor: placement.floor,
+        });
+    }
+}
+
+// Sprites del nivel, o uno aleatorio si el nivel no define ninguno
+fn place_sprites(sprite_renderer: &mut SpriteRenderer, level: &Level) {
+    let maze = level.maze();
+    sprite_renderer.clear_sprites();
+    add_level_sprites(sprite_renderer, level);
+    if level.sprites.is_empty() {
+        // Solo en casillas a las que el jugador puede llegar
+        let spawn = (level.spawn.0 as i32, level.spawn.1 as i32);
+        let cells: Vec<_> = reachable(maze, spawn)
+            .into_iter()
+            .filter(|&(x, y)| (x, y) != spawn && is_empty_cell(maze, x as f64 + 0.5, y as f64 + 0.5))
+            .collect();
+        if let Some(&(x, y)) = cells.choose(&mut rand::thread_rng()) {
+            sprite_renderer.add_sprite(Sprite { x: x as f64 + 0.5, y: y as f64 + 0.5, texture_index: 1, key: None, floor: level.floor });
+        }
+    }
+}
+
+// Un mapa de luz por piso; ninguno si el nivel no tiene luces ni cambia la luz ambiente
+fn bake_light_maps(level: &Level) -> Vec<LightMap> {
+    if level.lights.is_empty() && level.ambient == 1.0 {
+        return Vec::new();
+    }
+    level.floors.iter()
+        .enumerate()
+        .map(|(floor, maze)| {
+            let lights: Vec<Light> = level.lights.iter().filter(|light| light.floor == floor).copied().collect();
+            LightMap::bake(maze, &lights, level.ambient)
+        })
+        .collect()
+}
+
+// Vista 3D del piso actual del nivel: fondo o piso/techo, paredes y sprites
+fn draw_world(
+    framebuffer: &mut Framebuffer,
+    level: &Level,
+    player: &Player,
+    wall_textures: &WallTextures,
+    floor_textures: &FloorTextures,
+    sprite_renderer: &SpriteRenderer,
+    light_maps: &[LightMap],
+) {
+    let (sky, floor) = (level.sky_color, level.floor_color);
+    let sky = Color::RGB(sky.0, sky.1, sky.2);
+    let floor = Color::RGB(floor.0, floor.1, floor.2);
+    let env = Environment { fog: level.fog.as_ref(), lights: light_maps.get(level.floor) };
+    // Con niebla o luces el piso y el techo cambian de color por casilla aunque no tengan textura
+    if floor_textures.is_empty() && env.fog.is_none() && env.lights.is_none() {
+        draw_background(framebuffer, sky, floor);
+    } else {
+        render_planes(framebuffer, level.maze(), player, floor_textures, sky, floor, env);
+    }
+    render_scene(framebuffer, level.maze(), player, wall_textures, level.side_shade, env);
+
+    let view = View::new(player, FOV);
+    sprite_renderer.draw_sprites(framebuffer, level.maze(), level.floor, &view, env);
+}
+
+// --render-still <nivel> --out <imagen.png> [--pos x,y] [--angle grados] [--floor N] [--size AxB]
+// Dibuja una vista del nivel sin abrir ventana ni audio (para comparar imágenes en pruebas)
+fn render_still_command(args: &[String]) -> Result<(), String> {
+    let usage = "uso: --render-still <nivel> --out <imagen.png> [--pos x,y] [--angle grados] [--floor N] [--size AxB]";
+    let path = args.first().ok_or(usage)?;
+    let mut level = load_level(Path::new(path))?;
+    let mut player = Player::new(level.spawn.0, level.spawn.1, level.facing);
+    let (mut width, mut height) = (SCREEN_WIDTH, SCREEN_HEIGHT);
+    let mut out = None;
+
+    let mut options = args[1..].iter();
+    while let Some(flag) = options.next() {
+        let value = options.next().ok_or_else(|| format!("falta el valor de {}\n{}", flag, usage))?;
+        let bad_value = || format!("valor inválido para {}: {}", flag, value);
+        match flag.as_str() {
+            "--pos" => {
+                let (x, y) = value.split_once(',').ok_or_else(bad_value)?;
+                player.x = x.trim().parse().map_err(|_| bad_value())?;
+                player.y = y.trim().parse().map_err(|_| bad_value())?;
+            }
+            "--angle" => player.dir_angle = value.parse::<f64>().map_err(|_| bad_value())?.to_radians(),
+            "--floor" => {
+                level.floor = value.parse().map_err(|_| bad_value())?;
+                if level.floor >= level.floors.len() {
+                    return Err(format!("el nivel tiene {} piso(s): {}", level.floors.len(), value));
+                }
+            }
+            "--size" => {
+                let (w, h) = value.split_once('x').ok_or_else(bad_value)?;
+                width = w.parse().map_err(|_| bad_value())?;
+                height = h.parse().map_err(|_| bad_value())?;
+            }
+            "--out" => out = Some(value.clone()),
+            _ => return Err(format!("opción desconocida: {}\n{}", flag, usage)),
+        }
+    }
+    let out = out.ok_or(usage)?;
+    if level.maze().is_solid_at(player.x, player.y) {
+        return Err(format!("({}, {}) está dentro de una pared", player.x, player.y));
+    }
+    // Sin el suavizado del juego: la cámara ya está a la altura del piso
+    player.z = level.maze().elevation_at(player.x, player.y);
+
+    let wall_textures = WallTextures::load(level.maze().tiles())?;
+    let floor_textures = FloorTextures::load(level.maze().tiles())?;
+    // Solo los sprites del nivel, sin el aleatorio, para que la imagen sea siempre la misma
+    let mut sprite_renderer = load_sprite_textures()?;
+    add_level_sprites(&mut sprite_renderer, &level);
+
+    let mut framebuffer = Framebuffer::new(width, height);
+    // Las luces que titilan quedan como en el primer instante del nivel
+    let light_maps = bake_light_maps(&level);
+    draw_world(&mut framebuffer, &level, &player, &wall_textures, &floor_textures, &sprite_renderer, &light_maps);
+    framebuffer.save_png(Path::new(&out))?;
+    println!("{}: {}x{} desde ({}, {})", out, width, height, player.x, player.y);
+    Ok(())
+}
+
+// Elige el pack de `--pack <nombre>`, o el primero encontrado
+fn select_pack(args: &[String]) -> Result<LevelPack, String> {
+    let packs = discover_packs(Path::new(MAZE_DIR));
+    let names: Vec<&str> = packs.iter().map(|p| p.name.as_str()).collect();
+
+    if args.iter().any(|a| a == "--list-packs") {
+        for pack in &packs {
+            println!("{} ({} niveles)", pack.name, pack.levels.len());
+        }
+        std::process::exit(0);
+    }
+
+    match args.iter().position(|a| a == "--pack") {
+        Some(i) => {
+            let name = args.get(i + 1).ok_or("uso: --pack <nombre>")?;
+            let list = names.join(", ");
+            packs.into_iter().find(|p| &p.name == name)
+                .ok_or_else(|| format!("no existe el pack {:?}; disponibles: {}", name, list))
+        }
+        None => packs.into_iter().next()
+            .ok_or_else(|| format!("no se encontraron niveles en {}", MAZE_DIR)),
+    }
+}
+
+// Cambia el nivel por su versión recién cargada de disco, manteniendo al jugador y su piso.
+// Si ahora quedó dentro de una pared, se mueve a la casilla libre más cercana. Los sprites
+// se vuelven a poner, menos las llaves que el jugador ya recogió.
+fn reload_level(level: &mut Level, player: &mut Player, sprite_renderer: &mut SpriteRenderer, new_level: Level) {
+    let floor = level.floor.min(new_level.floors.len() - 1);
+    *level = new_level;
+    level.floor = floor;
+    if level.maze().is_solid_at(player.x, player.y)
+        && let Some((x, y)) = nearest_open_cell(level.maze(), (player.x as i32, player.y as i32))
+    {
+        player.x = x as f64 + 0.5;
+        player.y = y as f64 + 0.5;
+    }
+    place_sprites(sprite_renderer, level);
+    sprite_renderer.retain_sprites(|s| s.key.as_deref().is_none_or(|key| !player.inventory.has_key(key)));
+}
 
 fn main() -> Result<(), String> {
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("--check-maze") {
+        return check_mazes(&args[2..]);
+    }
+    if args.get(1).map(String::as_str) == Some("--generate-maze") {
+        return generate_maze_command(&args[2..]);
+    }
+    if args.get(1).map(String::as_str) == Some("--render-still") {
+        return render_still_command(&args[2..]);
+    }
+    let pack = select_pack(&args[1..])?;
+    println!("Pack: {}", pack.name);
+
     // SDL Init
     let sdl_context = sdl2::init()?;
     let video_subsystem = sdl_context.video()?;
@@ -31,9 +344,10 @@ fn main() -> Result<(), String> {
     sdl2::mixer::open_audio(44100, sdl2::mixer::AUDIO_S16LSB, 2, 1024)?;
     sdl2::mixer::allocate_channels(4);
 
-    let music = sdl2::mixer::Music::from_file("../assets/music/taylor.mp3")?;
+    // La música se carga con cada nivel; solo cambia si el nivel pide otra pista
+    let mut music: Option<(PathBuf, sdl2::mixer::Music)> = None;
     let pickup_sound = sdl2::mixer::Chunk::from_file("../assets/sfx/pickup.mp3")?;
-    music.play(-1)?;
+    let teleport_sound = sdl2::mixer::Chunk::from_file("../assets/sfx/teleport.wav")?;
 
     // Ventana
     let window = video_subsystem
@@ -44,80 +358,84 @@ fn main() -> Result<(), String> {
 
     let mut canvas = window.into_canvas().present_vsync().build().map_err(|e| e.to_string())?;
     let texture_creator = canvas.texture_creator();
+    // El juego se dibuja en el framebuffer y se sube a esta textura una vez por frame
+    let mut framebuffer = Framebuffer::new(SCREEN_WIDTH, SCREEN_HEIGHT);
+    let mut screen_texture = texture_creator
+        .create_texture_streaming(PixelFormatEnum::RGB24, SCREEN_WIDTH, SCREEN_HEIGHT)
+        .map_err(|e| e.to_string())?;
 
     // Sprite Renderer
-    let mut sprite_renderer: SpriteRenderer = SpriteRenderer::new();
-    let album_texture = texture_creator.load_texture("../assets/sprites/album.png")?;
-    sprite_renderer.add_texture(album_texture);
-    let hs_texture = texture_creator.load_texture("../assets/sprites/hs.png")?;
-    sprite_renderer.add_texture(hs_texture);
+    let mut sprite_renderer = load_sprite_textures()?;
 
     // Pantallas
     let inicio_image = texture_creator.load_texture("../assets/pages/inicio.png")?;
     let instrucciones_image = texture_creator.load_texture("../assets/pages/instrucciones.png")?;
     let victoria_image = texture_creator.load_texture("../assets/pages/victoria.png")?;
-    
-    // Pantallas de nivel
-    let level_images = vec![
-        None, // nivel 1 no tiene imagen porque usamos inicio/instrucciones
-        Some(texture_creator.load_texture("../assets/pages/level2.png")?),
-        Some(texture_creator.load_texture("../assets/pages/level3.png")?),
-    ];
-
-    // Niveles
-    let levels = vec![
-        "../maze/maze1.txt",
-        "../maze/maze2.txt",
-        "../maze/maze3.txt"
-    ];
 
     // Fuente
     let font = ttf_context.load_font("/System/Library/Fonts/Supplemental/Arial.ttf", 24)?;
 
-    // Texturas paredes
-    let mut wall_textures = Vec::new();
-    for i in 1..=10 {
-        let path = format!("../assets/tx/{}.png", i);
-        let texture = texture_creator.load_texture(path)?;
-        wall_textures.push(texture);
-    }
-
     // Mouse
     let mouse_util = sdl_context.mouse();
     mouse_util.set_relative_mouse_mode(true);
     mouse_util.show_cursor(false);
 
-    const FOV: f64 = std::f64::consts::PI / 3.0;
-
     let mut event_pump = sdl_context.event_pump()?;
 
     // -----> Bucle de niveles <-----
-    for (i, level_path) in levels.iter().enumerate() {
-        println!("Cargando nivel {}", i + 1);
+    for (i, level_path) in pack.levels.iter().enumerate() {
+        // cargar nivel
+        let mut level = load_level(level_path)?;
+        println!("Cargando nivel {}: {}", i + 1, level.name);
+
+        // Pantalla de "Nivel X" (el nivel 1 usa inicio/instrucciones).
+        // Si la imagen no se puede cargar, se salta la pantalla.
+        let level_image = level.title_card.as_ref().and_then(|path| {
+            texture_creator.load_texture(path)
+                .map_err(|e| eprintln!("{}: {}", path.display(), e))
+                .ok()
+        });
+
+        // Música del nivel
+        let level_music = level.music.clone().unwrap_or_else(|| PathBuf::from(DEFAULT_MUSIC));
+        if music.as_ref().map(|(path, _)| path) != Some(&level_music) {
+            let track = sdl2::mixer::Music::from_file(&level_music)?;
+            track.play(-1)?;
+            music = Some((level_music, track));
+        }
 
-        // cargar maze
-        let _maze = load_maze_from_file(level_path)?;
+        // Texturas paredes
+        let mut wall_textures = WallTextures::load(level.maze().tiles())?;
+        let mut floor_textures = FloorTextures::load(level.maze().tiles())?;
+        let mut light_maps = bake_light_maps(&level);
 
         // estado jugador nuevo en cada nivel
-        let mut player = Player::new();
+        let mut player = Player::new(level.spawn.0, level.spawn.1, level.facing);
+        place_sprites(&mut sprite_renderer, &level);
 
-        // sprites aleatorios en el mapa
-        loop {
-            let x = rand::random::<f64>() * (maze::MAP_WIDTH as f64);
-            let y = rand::random::<f64>() * (maze::MAP_HEIGHT as f64);
-            if is_empty_cell(x, y) {
-                sprite_renderer.add_sprite(Sprite { x, y, texture_index: 1 });
-                break;
-            }
-        }
+        // Recarga en caliente de los archivos del nivel
+        let mut watcher = FileWatcher::new(&level.sources);
+
+        // Casilla actual, para disparar los eventos de la tabla solo al entrar
+        let mut current_cell = (player.x as i32, player.y as i32);
+
+        // Piso que muestra el minimapa (Tab para ver los demás)
+        let mut map_floor = level.floor;
+
+        // Tiempo que le queda al destello del teleportador
+        let mut flash = 0.0;
+        // Aviso en pantalla y los segundos que le quedan
+        let mut message: Option<(String, f64)> = None;
 
         let mut last_time = Instant::now();
+        // Segundos desde que empezó el nivel (para el titileo de las luces)
+        let mut level_time = 0.0;
         let mut victoria = false;
         let mut inicio = i == 0; // solo en el primer nivel
         let mut instrucciones = false;
 
         // ------ Pantalla de "Nivel X" ------
-        if let Some(level_img) = &level_images[i] {
+        if let Some(level_img) = &level_image {
             'level_screen: loop {
                 for event in event_pump.poll_iter() {
                     match event {
@@ -141,15 +459,44 @@ fn main() -> Result<(), String> {
             let now = Instant::now();
             let delta_time = now.duration_since(last_time).as_secs_f64();
             last_time = now;
-
-            let plane_x = -player.dir_angle.sin() * (FOV / 2.0).tan();
-            let plane_y = player.dir_angle.cos() * (FOV / 2.0).tan();
+            level_time += delta_time;
 
             // Eventos
             for event in event_pump.poll_iter() {
                 match event {
                     Event::Quit { .. } 
                     | Event::KeyDown { keycode: Some(Keycode::Escape), .. } => return Ok(()),
+                    // En la pantalla de inicio, R cambia el primer nivel por uno aleatorio
+                    Event::KeyDown { keycode: Some(Keycode::R), .. } if inicio => {
+                        let config = GeneratorConfig { seed: clock_seed(), ..GeneratorConfig::default() };
+                        level = Level::from_maze("Aleatorio", generate(&config));
+                        println!("Nivel aleatorio, semilla {}", config.seed);
+                        wall_textures = WallTextures::load(level.maze().tiles())?;
+                        floor_textures = FloorTextures::load(level.maze().tiles())?;
+                        light_maps = bake_light_maps(&level);
+                        player = Player::new(level.spawn.0, level.spawn.1, level.facing);
+                        place_sprites(&mut sprite_renderer, &level);
+                        watcher = FileWatcher::new(&level.sources);
+                        map_floor = level.floor;
+                    }
+                    // E: abrir/cerrar la puerta de enfrente
+                    Event::KeyDown { keycode: Some(Keycode::E), .. } if !inicio && !instrucciones => {
+                        let (x, y) = player.facing_cell();
+                        let floor = level.floor;
+                        let maze = level.maze_mut();
+                        if player.is_locked_out(maze, x, y) {
+                            let text = format!("Necesitás la llave {}", maze.door_lock(x, y).unwrap_or_default());
+                            message = Some((text, MESSAGE_TIME));
+                        } else if !maze.use_door(x, y)
+                            && maze.push_wall(x, y, player.facing_dir(), |x, y| sprite_renderer.occupies(x, y, floor))
+                        {
+                            message = Some(("¡Pasaje secreto!".to_string(), MESSAGE_TIME));
+                        }
+                    }
+                    // Tab: ver otro piso en el minimapa
+                    Event::KeyDown { keycode: Some(Keycode::Tab), .. } if !inicio && !instrucciones => {
+                        map_floor = (map_floor + 1) % level.floors.len();
+                    }
                     Event::KeyDown { keycode: Some(Keycode::Return), .. } => {
                         if inicio {
                             inicio = false;
@@ -180,62 +527,156 @@ fn main() -> Result<(), String> {
                 continue;
             }
 
+            // Recargar el nivel si se editó en disco; si el archivo nuevo tiene errores se sigue con el anterior
+            if watcher.poll()
+                && let Some(path) = level.sources.first().cloned()
+            {
+                match load_level(&path) {
+                    Ok(new_level) => {
+                        println!("Nivel recargado: {}", path.display());
+                        reload_level(&mut level, &mut player, &mut sprite_renderer, new_level);
+                        map_floor = map_floor.min(level.floors.len() - 1);
+                        wall_textures = WallTextures::load(level.maze().tiles())?;
+                        floor_textures = FloorTextures::load(level.maze().tiles())?;
+                        light_maps = bake_light_maps(&level);
+                        watcher = FileWatcher::new(&level.sources);
+                    }
+                    Err(e) => eprintln!("{}", e),
+                }
+            }
+
             // Movimiento jugador
             let keys: Vec<Keycode> = event_pump
                 .keyboard_state()
                 .pressed_scancodes()
                 .filter_map(Keycode::from_scancode)
                 .collect();
-            player.update_position(&keys, delta_time);
-
-            let mouse_state = event_pump.relative_mouse_state();
-            player.rotate(mouse_state.x());
+            player.update_position(level.maze(), &keys, delta_time);
+            // Las puertas y paredes secretas de los otros pisos también terminan de moverse
+            let current = level.floor;
+            for (floor, maze) in level.floors.iter_mut().enumerate() {
+                let occupied = if floor == current { (player.x as i32, player.y as i32) } else { (-1, -1) };
+                maze.update(delta_time, occupied);
+            }
 
-            // Comprobar meta (casilla 5 = salida)
-            let mut picked_album = false;
-            unsafe {
-                if maze::MAP[player.y as usize][player.x as usize] == 5 && !picked_album {
-                    // reproducir sonido
+            // Recoger llaves
+            for item in sprite_renderer.take_pickups(player.x, player.y, level.floor) {
+                if let Some(color) = item.key {
                     sdl2::mixer::Channel::all().play(&pickup_sound, 0)?;
+                    println!("Llave {} recogida", color);
+                    player.inventory.add_key(&color);
+                }
+            }
 
-                    // agregar sprite de album
-                    sprite_renderer.add_sprite(Sprite {
-                        x: player.x as f64 + 0.5,
-                        y: player.y as f64 + 0.5,
-                        texture_index: 0,
-                    });
+            let mouse_state = event_pump.relative_mouse_state();
+            player.rotate(mouse_state.x());
 
-                    picked_album = true;
-                    victoria = true;
+            let cell = (player.x as i32, player.y as i32);
+            if cell != current_cell {
+                current_cell = cell;
+                if let Some(text) = level.maze().trigger(cell.0, cell.1).and_then(|t| level.events.get(t)) {
+                    message = Some((text.clone(), MESSAGE_TIME));
+                }
+                // Escaleras y ascensores: se aparece en la misma casilla del otro piso
+                if let Some(floor) = level.link_target(cell.0, cell.1) {
+                    level.floor = floor;
+                    map_floor = floor;
+                } else if let Some(&pad) = level.teleport_exit(cell.0, cell.1) {
+                    // Teleportador: a la otra punta, que cuenta como casilla actual para no rebotar
+                    level.floor = pad.floor;
+                    map_floor = pad.floor;
+                    player.x = pad.x as f64 + 0.5;
+                    player.y = pad.y as f64 + 0.5;
+                    if let Some(facing) = pad.facing {
+                        player.dir_angle = facing.to_radians();
+                    }
+                    current_cell = (pad.x, pad.y);
+                    flash = TELEPORT_FLASH;
+                    sdl2::mixer::Channel::all().play(&teleport_sound, 0)?;
                 }
             }
+            flash = (flash - delta_time).max(0.0);
+            message = message.filter(|(_, time)| *time > delta_time).map(|(text, time)| (text, time - delta_time));
+
+            // Comprobar meta (casilla de salida de la tabla)
+            if level.maze().is_exit_at(player.x, player.y) {
+                // reproducir sonido
+                sdl2::mixer::Channel::all().play(&pickup_sound, 0)?;
+
+                // agregar sprite de album
+                sprite_renderer.add_sprite(Sprite {
+                    x: player.x + 0.5,
+                    y: player.y + 0.5,
+                    texture_index: 0,
+                    key: None,
+                    floor: level.floor,
+                });
+
+                victoria = true;
+            }
 
             if victoria {
                 // Mostrar victoria solo si es el último nivel
-                if i == levels.len() - 1 {
+                if i == pack.levels.len() - 1 {
                     canvas.copy(&victoria_image, None, Some(Rect::new(0, 0, SCREEN_WIDTH, SCREEN_HEIGHT)))?;
-                    canvas.present();
-                    std::thread::sleep(Duration::from_secs(2));
                 } else {
                     // Sonido de victoria para niveles intermedios sin mostrar la pantalla
                     sdl2::mixer::Channel::all().play(&pickup_sound, 0)?;
+                    canvas.set_draw_color(Color::RGB(0, 0, 0));
+                    canvas.clear();
                 }
+                // Resumen del nivel
+                let (found, total) = level.secrets();
+                let summary = [
+                    format!("{} completado", level.name),
+                    format!("Secretos: {}/{}", found, total),
+                ];
+                draw_level_summary(&mut canvas, &font, &texture_creator, &summary, SCREEN_WIDTH, SCREEN_HEIGHT)?;
+                canvas.present();
+                std::thread::sleep(Duration::from_secs(2));
                 break 'level_loop;
             }
 
 
             // Render juego
-            draw_background(&mut canvas, SCREEN_WIDTH, SCREEN_HEIGHT)?;
-            render_scene(&mut canvas, &player, &wall_textures, SCREEN_WIDTH, SCREEN_HEIGHT)?;
-            sprite_renderer.draw_sprites(
-                &mut canvas,
-                player.x, player.y,
-                player.dir_angle, plane_x, plane_y,
-                SCREEN_WIDTH, SCREEN_HEIGHT,
-            )?;
-            draw_minimap(&mut canvas, &player, SCREEN_WIDTH)?;
+            for light_map in &mut light_maps {
+                light_map.update(level_time);
+            }
+            draw_world(&mut framebuffer, &level, &player, &wall_textures, &floor_textures, &sprite_renderer, &light_maps);
+            draw_flash(&mut framebuffer, Color::RGB(150, 230, 255), flash / TELEPORT_FLASH);
+            // Mantener H muestra el camino a la salida en el minimapa (si está en este piso).
+            // El camino sigue las reglas del movimiento: no pasa por puertas cerradas ni por
+            // cerraduras sin la llave. Con teleportadores se usa BFS: la heurística de A* deja de servir.
+            let on_floor = map_floor == level.floor;
+            let hint = if keys.contains(&Keycode::H) && on_floor {
+                let maze = level.maze();
+                let player_cell = (player.x as i32, player.y as i32);
+                let passable = |(x, y)| !maze.blocks(x, y) && !player.is_locked_out(maze, x, y);
+                let teleport = |(x, y)| level.teleport_exit(x, y)
+                    .filter(|pad| pad.floor == level.floor)
+                    .map(|pad| (pad.x, pad.y));
+                find_exit(maze).and_then(|exit| if level.teleporters.is_empty() {
+                    astar_path(maze, player_cell, exit, passable)
+                } else {
+                    bfs_path_linked(maze, player_cell, exit, teleport, passable)
+                })
+            } else {
+                None
+            };
+            let shown = &level.floors[map_floor];
+            draw_minimap(&mut framebuffer, shown, on_floor.then_some(&player), hint.as_deref());
+            draw_floor_indicator(&mut framebuffer, level.floors.len(), level.floor, map_floor);
+            let key_icons: Vec<_> = player.inventory.keys()
+                .iter()
+                .filter_map(|color| sprite_renderer.texture(key_texture(color)))
+                .collect();
+            draw_inventory(&mut framebuffer, &key_icons);
+            framebuffer.present(&mut canvas, &mut screen_texture)?;
             let fps = (1.0 / delta_time) as i32;
             draw_fps(&mut canvas, &font, &texture_creator, fps)?;
+            if let Some((text, _)) = &message {
+                draw_message(&mut canvas, &font, &texture_creator, text, SCREEN_WIDTH, SCREEN_HEIGHT)?;
+            }
             canvas.present();
             std::thread::sleep(Duration::from_millis(65));
         }
@@ -1,39 +1,556 @@
 // maze.rs
-use std::fs::File;
-use std::io::{BufRead, BufReader};
-
-pub const MAP_WIDTH: usize = 16;
-pub const MAP_HEIGHT: usize = 16;
-
-pub static mut MAP: [[u8; MAP_WIDTH]; MAP_HEIGHT] = [[0; MAP_WIDTH]; MAP_HEIGHT];
-
-pub fn load_maze_from_file(filename: &str) -> Result<(), String> {
-    let file = File::open(filename).map_err(|e| e.to_string())?;
-    let reader = BufReader::new(file);
-    
-    unsafe {
-        MAP = [[0; MAP_WIDTH]; MAP_HEIGHT];
-        
-        for (y, line) in reader.lines().enumerate() {
-            if y >= MAP_HEIGHT {
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::sync::Arc;
+use crate::door::{self, Door};
+use crate::pathfinding;
+use crate::pushwall::{self, PushWall};
+use crate::segment::{Point, Segment};
+use crate::tiles::{FloorLink, TileDef, TileSet};
+
+// Casilla de salida/meta en la tabla por defecto (assets/tiles.toml)
+pub const EXIT: u8 = 5;
+
+// Casilla donde aparece el jugador (ver Player::new)
+pub const SPAWN: (usize, usize) = (1, 1);
+
+// Lo más alto que se puede subir de una casilla a la vecina (pisos elevados)
+pub const MAX_STEP: f64 = 0.5;
+
+// Errores de validación. Las posiciones son línea/columna del archivo, empezando en 1.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MazeError {
+    Empty,
+    BadGlyph { line: usize, col: usize, glyph: char },
+    MissingExit,
+    DuplicateExit { line: usize, col: usize },
+    OpenBorder { line: usize, col: usize },
+    SpawnInWall { line: usize, col: usize },
+    ExitUnreachable { line: usize, col: usize },
+}
+
+impl MazeError {
+    pub fn position(&self) -> Option<(usize, usize)> {
+        match *self {
+            MazeError::BadGlyph { line, col, .. }
+            | MazeError::DuplicateExit { line, col }
+            | MazeError::OpenBorder { line, col }
+            | MazeError::SpawnInWall { line, col }
+            | MazeError::ExitUnreachable { line, col } => Some((line, col)),
+            MazeError::Empty | MazeError::MissingExit => None,
+        }
+    }
+
+    // Formato "archivo:línea:columna: mensaje", como los compiladores
+    pub fn report(&self, filename: &str) -> String {
+        match self.position() {
+            Some((line, col)) => format!("{}:{}:{}: {}", filename, line, col, self),
+            None => format!("{}: {}", filename, self),
+        }
+    }
+}
+
+impl fmt::Display for MazeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MazeError::Empty => write!(f, "el laberinto está vacío"),
+            MazeError::BadGlyph { glyph, .. } => write!(f, "carácter inválido {:?}", glyph),
+            MazeError::MissingExit => write!(f, "no hay casilla de salida"),
+            MazeError::DuplicateExit { .. } => write!(f, "salida duplicada, solo se permite una"),
+            MazeError::OpenBorder { .. } => write!(f, "el borde no está cerrado"),
+            MazeError::SpawnInWall { .. } => write!(f, "el inicio del jugador está dentro de una pared"),
+            MazeError::ExitUnreachable { .. } => write!(f, "la salida no se puede alcanzar desde el inicio"),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Maze {
+    width: usize,
+    height: usize,
+    cells: Vec<u8>,
+    tiles: Arc<TileSet>,
+    doors: HashMap<(i32, i32), Door>,   // solo las puertas que ya se usaron
+    pushwalls: Vec<PushWall>,           // paredes secretas en movimiento
+    moved_walls: HashSet<(i32, i32)>,   // paredes secretas que ya se empujaron y quedaron quietas
+    secrets_found: usize,
+}
+
+impl Maze {
+    // Laberinto vacío con la tabla de casillas por defecto
+    pub fn new(width: usize, height: usize) -> Self {
+        Maze::with_tiles(width, height, TileSet::builtin())
+    }
+
+    pub fn with_tiles(width: usize, height: usize, tiles: Arc<TileSet>) -> Self {
+        Maze {
+            width,
+            height,
+            cells: vec![0; width * height],
+            tiles,
+            doors: HashMap::new(),
+            pushwalls: Vec::new(),
+            moved_walls: HashSet::new(),
+            secrets_found: 0,
+        }
+    }
+
+    pub fn tiles(&self) -> &TileSet {
+        &self.tiles
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    // Valor de la casilla, None si está fuera del mapa
+    pub fn get(&self, x: i32, y: i32) -> Option<u8> {
+        if x < 0 || y < 0 || x as usize >= self.width() || y as usize >= self.height() {
+            return None;
+        }
+        Some(self.cells[y as usize * self.width + x as usize])
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, value: u8) {
+        if x < self.width() && y < self.height() {
+            self.cells[y * self.width + x] = value;
+        }
+    }
+
+    // Definición de la casilla, None fuera del mapa o si el valor no está en la tabla
+    pub fn tile(&self, x: i32, y: i32) -> Option<&TileDef> {
+        self.get(x, y).and_then(|cell| self.tiles.get(cell))
+    }
+
+    // Fuera del mapa y los valores sin definir también cuentan como pared
+    pub fn is_solid(&self, x: i32, y: i32) -> bool {
+        self.tile(x, y).is_none_or(|tile| tile.solid)
+    }
+
+    pub fn is_exit(&self, x: i32, y: i32) -> bool {
+        self.tile(x, y).is_some_and(|tile| tile.exit)
+    }
+
+    // Suelo sin nada especial: ni pared, ni salida, ni evento, ni puerta
+    pub fn is_empty(&self, x: i32, y: i32) -> bool {
+        self.tile(x, y).is_some_and(|tile| !tile.solid && !tile.exit && tile.trigger.is_none() && !tile.door && tile.floor_link.is_none() && !tile.teleporter && tile.segments.is_empty())
+    }
+
+    pub fn is_door(&self, x: i32, y: i32) -> bool {
+        self.tile(x, y).is_some_and(|tile| tile.door)
+    }
+
+    // Color de la llave que abre la puerta de la casilla, si tiene cerradura
+    pub fn door_lock(&self, x: i32, y: i32) -> Option<&str> {
+        self.tile(x, y).and_then(|tile| tile.lock.as_deref())
+    }
+
+    // Cuánto está abierta la puerta de la casilla (0 = cerrada)
+    pub fn door_open(&self, x: i32, y: i32) -> f64 {
+        self.doors.get(&(x, y)).map_or(0.0, |d| d.open)
+    }
+
+    // Paredes, puertas que todavía no están abiertas y paredes secretas en movimiento.
+    // Los caminos (pathfinding) sí cruzan las puertas; el jugador solo cuando ya se abrieron.
+    pub fn blocks(&self, x: i32, y: i32) -> bool {
+        self.is_solid(x, y)
+            || (self.is_door(x, y) && self.door_open(x, y) < door::PASSABLE)
+            || self.pushwalls.iter().any(|w| w.covers(x, y))
+    }
+
+    // Pared secreta que todavía no se empujó
+    pub fn is_secret(&self, x: i32, y: i32) -> bool {
+        self.tile(x, y).is_some_and(|tile| tile.secret) && !self.moved_walls.contains(&(x, y))
+    }
+
+    // Empuja la pared secreta de (x, y) en la dirección `dir`. false si no es secreta
+    // o no tiene espacio libre detrás. Solo se desliza sobre suelo vacío (ver is_empty) y
+    // sin pasar por las casillas en que `occupied` da true (las que tienen sprites).
+    // Donde estaba queda el suelo que la pared va a tapar al llegar.
+    pub fn push_wall(&mut self, x: i32, y: i32, dir: (i32, i32), occupied: impl Fn(i32, i32) -> bool) -> bool {
+        if !self.is_secret(x, y) {
+            return false;
+        }
+        let mut distance = 0;
+        while distance < pushwall::PUSH_DISTANCE {
+            let (nx, ny) = (x + dir.0 * (distance + 1), y + dir.1 * (distance + 1));
+            if !self.is_empty(nx, ny) || self.blocks(nx, ny) || occupied(nx, ny) {
                 break;
             }
-            
-            let line = line.map_err(|e| e.to_string())?;
-            for (x, ch) in line.chars().enumerate() {
-                if x >= MAP_WIDTH {
-                    break;
+            distance += 1;
+        }
+        let Some(floor) = self.get(x + dir.0 * distance, y + dir.1 * distance).filter(|_| distance > 0) else {
+            return false;
+        };
+
+        let tile = self.cells[y as usize * self.width + x as usize];
+        self.set(x as usize, y as usize, floor);
+        self.pushwalls.push(PushWall::new((x, y), dir, tile, distance));
+        self.secrets_found += 1;
+        true
+    }
+
+    // Pared secreta en movimiento sobre la casilla: esquina del bloque y casilla que dibuja
+    pub fn moving_wall(&self, x: i32, y: i32) -> Option<((f64, f64), u8)> {
+        self.pushwalls.iter().find(|w| w.covers(x, y)).map(|w| (w.position(), w.tile))
+    }
+
+    // (encontrados, total) de paredes secretas
+    pub fn secrets(&self) -> (usize, usize) {
+        let mut hidden = 0;
+        for y in 0..self.height as i32 {
+            for x in 0..self.width as i32 {
+                if self.is_secret(x, y) {
+                    hidden += 1;
                 }
-                
-                MAP[y][x] = match ch {
-                    '0' | ' ' => 0,  // espacio vacío
-                    '5' => 5,  // meta/salida (ANTES de '1'..='9')
-                    '1'..='9' => ch.to_digit(10).unwrap() as u8,  // paredes con textura
-                    _ => 0,
-                };
             }
         }
+        (self.secrets_found, self.secrets_found + hidden)
+    }
+
+    // Abre o cierra la puerta de la casilla. false si ahí no hay puerta.
+    pub fn use_door(&mut self, x: i32, y: i32) -> bool {
+        if !self.is_door(x, y) {
+            return false;
+        }
+        self.doors.entry((x, y)).or_insert_with(Door::new).activate();
+        true
+    }
+
+    // Avanza puertas y paredes secretas; la casilla `occupied` (la del jugador) no se puede cerrar
+    pub fn update(&mut self, delta_time: f64, occupied: (i32, i32)) {
+        for (&cell, door) in self.doors.iter_mut() {
+            door.update(delta_time, cell == occupied);
+        }
+
+        let mut arrived = Vec::new();
+        self.pushwalls.retain_mut(|wall| {
+            let done = wall.update(delta_time);
+            if done {
+                arrived.push((wall.target(), wall.tile));
+            }
+            !done
+        });
+        for ((x, y), tile) in arrived {
+            self.set(x as usize, y as usize, tile);
+            self.moved_walls.insert((x, y));
+        }
+    }
+
+    pub fn floor_link(&self, x: i32, y: i32) -> Option<FloorLink> {
+        self.tile(x, y).and_then(|tile| tile.floor_link)
+    }
+
+    // Altura del piso de la casilla (0 si no está definida)
+    pub fn elevation(&self, x: i32, y: i32) -> f64 {
+        self.tile(x, y).map_or(0.0, |tile| tile.elevation)
+    }
+
+    // Si se puede pasar de una casilla a la vecina sin que el escalón sea muy alto
+    pub fn can_step(&self, from: (i32, i32), to: (i32, i32)) -> bool {
+        self.elevation(to.0, to.1) - self.elevation(from.0, from.1) <= MAX_STEP + 1e-9
+    }
+
+    // Paredes finas o diagonales de la casilla, en coordenadas del mundo
+    pub fn segments(&self, x: i32, y: i32) -> impl Iterator<Item = Segment> + '_ {
+        self.tile(x, y).into_iter().flat_map(move |tile| tile.segments.iter().map(move |s| s.offset(x, y)))
+    }
+
+    // Si ir en línea recta de `from` a `to` atraviesa una pared fina. Se revisan las
+    // casillas del rectángulo entre los dos puntos (pensado para tramos cortos).
+    pub fn crosses_wall(&self, from: Point, to: Point) -> bool {
+        let (x0, x1) = (from.0.min(to.0).floor() as i32, from.0.max(to.0).floor() as i32);
+        let (y0, y1) = (from.1.min(to.1).floor() as i32, from.1.max(to.1).floor() as i32);
+        (y0..=y1).any(|y| (x0..=x1).any(|x| self.segments(x, y).any(|s| s.crosses(from, to))))
+    }
+
+    // Distancia a la pared fina más cercana en la casilla de `p` y sus vecinas
+    pub fn wall_distance(&self, p: Point) -> f64 {
+        let (cx, cy) = (p.0.floor() as i32, p.1.floor() as i32);
+        (cy - 1..=cy + 1)
+            .flat_map(|y| (cx - 1..=cx + 1).map(move |x| (x, y)))
+            .flat_map(|(x, y)| self.segments(x, y))
+            .map(|s| s.distance_to(p))
+            .fold(f64::INFINITY, f64::min)
+    }
+
+    pub fn is_teleporter(&self, x: i32, y: i32) -> bool {
+        self.tile(x, y).is_some_and(|tile| tile.teleporter)
+    }
+
+    pub fn trigger(&self, x: i32, y: i32) -> Option<&str> {
+        self.tile(x, y).and_then(|tile| tile.trigger.as_deref())
+    }
+
+    // Texto en el mismo formato que los .txt de maze/
+    pub fn to_text(&self) -> String {
+        let mut text = String::with_capacity((self.width + 1) * self.height);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                text.push(self.tiles.glyph_for(self.cells[y * self.width + x]));
+            }
+            text.push('\n');
+        }
+        text
+    }
+
+    // Versiones en coordenadas del mundo (posición del jugador/sprites)
+    pub fn is_solid_at(&self, x: f64, y: f64) -> bool {
+        self.is_solid(x.floor() as i32, y.floor() as i32)
+    }
+
+    pub fn is_exit_at(&self, x: f64, y: f64) -> bool {
+        self.is_exit(x.floor() as i32, y.floor() as i32)
+    }
+
+    pub fn is_empty_at(&self, x: f64, y: f64) -> bool {
+        self.is_empty(x.floor() as i32, y.floor() as i32)
+    }
+
+    pub fn blocks_at(&self, x: f64, y: f64) -> bool {
+        self.blocks(x.floor() as i32, y.floor() as i32)
+    }
+
+    pub fn elevation_at(&self, x: f64, y: f64) -> f64 {
+        self.elevation(x.floor() as i32, y.floor() as i32)
+    }
+}
+
+// Convierte el texto en un laberinto usando la tabla `tiles`. Los caracteres que no
+// están en la tabla se reportan y se tratan como espacio vacío para poder seguir validando el resto.
+pub fn parse_maze(text: &str, tiles: Arc<TileSet>) -> (Maze, Vec<MazeError>) {
+    let mut errors = Vec::new();
+    let mut lines: Vec<&str> = text.lines().collect();
+
+    // Ignorar líneas vacías al final del archivo
+    while lines.last().is_some_and(|l| l.trim().is_empty()) {
+        lines.pop();
+    }
+
+    if lines.is_empty() {
+        return (Maze::with_tiles(0, 0, tiles), vec![MazeError::Empty]);
+    }
+
+    // El ancho es el de la fila más larga; las filas más cortas se rellenan con espacio vacío
+    let width = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+    let height = lines.len();
+
+    let mut maze = Maze::with_tiles(width, height, tiles.clone());
+
+    for (y, line) in lines.iter().enumerate() {
+        for (x, ch) in line.chars().enumerate() {
+            let value = match tiles.id_for(ch) {
+                Some(id) => id,
+                None => {
+                    errors.push(MazeError::BadGlyph { line: y + 1, col: x + 1, glyph: ch });
+                    0
+                }
+            };
+            maze.set(x, y, value);
+        }
+    }
+
+    (maze, errors)
+}
+
+// El borde solo puede tener paredes, si no los rayos se salen del mapa
+pub fn validate_border(maze: &Maze) -> Vec<MazeError> {
+    let (w, h) = (maze.width() as i32, maze.height() as i32);
+    if w == 0 || h == 0 {
+        return vec![MazeError::Empty];
+    }
+
+    let mut errors = Vec::new();
+    for y in 0..h {
+        for x in 0..w {
+            let on_border = x == 0 || y == 0 || x == w - 1 || y == h - 1;
+            if on_border && !maze.is_solid(x, y) {
+                errors.push(MazeError::OpenBorder { line: y as usize + 1, col: x as usize + 1 });
+            }
+        }
+    }
+    errors
+}
+
+// Casillas de salida, en orden de lectura
+pub fn exit_cells(maze: &Maze) -> Vec<(i32, i32)> {
+    (0..maze.height() as i32)
+        .flat_map(|y| (0..maze.width() as i32).map(move |x| (x, y)))
+        .filter(|&(x, y)| maze.is_exit(x, y))
+        .collect()
+}
+
+// Validaciones estructurales: una sola salida, borde cerrado,
+// inicio libre y salida alcanzable desde el inicio.
+pub fn validate_maze(maze: &Maze, spawn: (usize, usize)) -> Vec<MazeError> {
+    let mut errors = validate_border(maze);
+    if errors.contains(&MazeError::Empty) {
+        return errors;
+    }
+
+    let exits = exit_cells(maze);
+    match exits.len() {
+        0 => errors.push(MazeError::MissingExit),
+        _ => {
+            for &(x, y) in &exits[1..] {
+                errors.push(MazeError::DuplicateExit { line: y as usize + 1, col: x as usize + 1 });
+            }
+        }
+    }
+
+    let (sx, sy) = (spawn.0 as i32, spawn.1 as i32);
+    if maze.is_solid(sx, sy) {
+        errors.push(MazeError::SpawnInWall { line: spawn.1 + 1, col: spawn.0 + 1 });
+    } else {
+        let reachable = pathfinding::distances(maze, (sx, sy), |cell| pathfinding::keyless(maze, cell));
+        for &(x, y) in &exits {
+            if reachable[y as usize * maze.width() + x as usize].is_none() {
+                errors.push(MazeError::ExitUnreachable { line: y as usize + 1, col: x as usize + 1 });
+            }
+        }
+    }
+
+    errors
+}
+
+// Parsea y valida un texto con el inicio del jugador en `spawn`
+pub fn check_maze(text: &str, spawn: (usize, usize), tiles: Arc<TileSet>) -> (Maze, Vec<MazeError>) {
+    let (maze, mut errors) = parse_maze(text, tiles);
+    if !errors.contains(&MazeError::Empty) {
+        errors.extend(validate_maze(&maze, spawn));
+    }
+    (maze, errors)
+}
+
+// Lee y valida un archivo. Err solo si no se pudo leer; los problemas del laberinto vienen en el Vec.
+pub fn check_maze_file(filename: &str) -> Result<(Maze, Vec<MazeError>), String> {
+    let text = fs::read_to_string(filename).map_err(|e| format!("{}: {}", filename, e))?;
+    Ok(check_maze(&text, SPAWN, TileSet::builtin()))
+}
+
+// Une los errores en un solo mensaje, uno por línea
+pub fn report_errors(filename: &str, errors: &[MazeError]) -> String {
+    let report: Vec<String> = errors.iter().map(|e| e.report(filename)).collect();
+    report.join("\n")
+}
+
+pub fn save_maze_to_file(maze: &Maze, filename: &str) -> Result<(), String> {
+    fs::write(filename, maze.to_text()).map_err(|e| format!("{}: {}", filename, e))
+}
+
+pub fn load_maze_from_file(filename: &str) -> Result<Maze, String> {
+    let (maze, errors) = check_maze_file(filename)?;
+
+    if !errors.is_empty() {
+        return Err(report_errors(filename, &errors));
+    }
+
+    Ok(maze)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn maze(text: &str) -> Maze {
+        let (maze, errors) = parse_maze(text, TileSet::builtin());
+        assert!(errors.is_empty());
+        maze
+    }
+
+    #[test]
+    fn push_wall_slides_over_empty_floor_only() {
+        let mut maze = maze("1111111\n1S00001\n1S0>001\n1S_0001\n1111111\n");
+        let secret = maze.get(1, 1).unwrap();
+        let floor = maze.get(2, 1).unwrap();
+
+        assert!(maze.push_wall(1, 1, (1, 0), |_, _| false));
+        maze.update(10.0, (0, 0));
+        assert_eq!(maze.get(3, 1), Some(secret));
+        assert_eq!(maze.get(1, 1), Some(floor));
+
+        // La escalera lo frena a una casilla
+        assert!(maze.push_wall(1, 2, (1, 0), |_, _| false));
+        maze.update(10.0, (0, 0));
+        assert_eq!(maze.get(2, 2), Some(secret));
+        assert!(maze.floor_link(3, 2).is_some());
+
+        // Un sprite también la frena, y donde estaba queda el suelo que tapó al llegar
+        let raised = maze.get(2, 3).unwrap();
+        assert!(maze.push_wall(1, 3, (1, 0), |x, _| x == 3));
+        maze.update(10.0, (0, 0));
+        assert_eq!(maze.get(2, 3), Some(secret));
+        assert_eq!(maze.get(1, 3), Some(raised));
+    }
+
+    #[test]
+    fn push_wall_needs_room() {
+        let mut maze = maze("1111\n1S>1\n1S01\n1111\n");
+        assert!(!maze.push_wall(1, 1, (1, 0), |_, _| false));
+        assert!(!maze.push_wall(1, 2, (1, 0), |x, y| (x, y) == (2, 2)));
+        assert!(maze.is_secret(1, 1) && maze.is_secret(1, 2));
+    }
+
+    // Reporte de check_maze con el inicio en SPAWN, un error por línea
+    fn check(text: &str) -> String {
+        let (_, errors) = check_maze(text, SPAWN, TileSet::builtin());
+        report_errors("m.txt", &errors)
+    }
+
+    #[test]
+    fn reports_bad_glyph_position() {
+        assert_eq!(check("11111\n10051\n10?01\n11111\n"), "m.txt:3:3: carácter inválido '?'");
+    }
+
+    #[test]
+    fn reports_open_border_position() {
+        assert_eq!(check("11111\n10050\n11111\n"), "m.txt:2:5: el borde no está cerrado");
+    }
+
+    #[test]
+    fn reports_missing_exit() {
+        assert_eq!(check("1111\n1001\n1111\n"), "m.txt: no hay casilla de salida");
+    }
+
+    #[test]
+    fn reports_unreachable_exit_position() {
+        assert_eq!(
+            check("111111\n100101\n100151\n111111\n"),
+            "m.txt:3:5: la salida no se puede alcanzar desde el inicio",
+        );
+        // Sin llaves una puerta con cerradura no se abre
+        assert_eq!(
+            check("111111\n10r051\n111111\n"),
+            "m.txt:2:5: la salida no se puede alcanzar desde el inicio",
+        );
+    }
+
+    #[test]
+    fn parses_wide_maze_from_its_text() {
+        let wall = "1".repeat(40);
+        let middle = format!("1{}1", "0".repeat(38));
+        let exit_row = format!("1{}51", "0".repeat(37));
+        let text = format!("{}\n{}\n{}\n{}\n{}\n", wall, middle, middle, exit_row, wall);
+        let (maze, errors) = check_maze(&text, SPAWN, TileSet::builtin());
+        assert!(errors.is_empty());
+        assert_eq!((maze.width(), maze.height()), (40, 5));
+        assert_eq!(maze.get(39, 4), Some(1));
+        assert!(maze.is_exit(38, 3));
+        assert_eq!(maze.get(40, 0), None);
+        assert_eq!(maze.to_text().lines().count(), 5);
+    }
+
+    #[test]
+    fn ragged_rows_leave_the_border_open() {
+        // La fila corta se rellena con vacío, así que le falta la pared del borde
+        let (maze, errors) = parse_maze("11111\n1051\n11111\n", TileSet::builtin());
+        assert!(errors.is_empty());
+        assert_eq!((maze.width(), maze.height()), (5, 3));
+        assert_eq!(report_errors("m.txt", &validate_border(&maze)), "m.txt:2:5: el borde no está cerrado");
     }
-    
-    Ok(())
-}
\ No newline at end of file
+}
@@ -0,0 +1,242 @@
+// pack.rs
+use serde::Deserialize;
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const MANIFEST: &str = "pack.toml";
+// Lo que puede ser un nivel en una carpeta sin manifiesto (además de los .txt)
+const LEVEL_EXTENSIONS: [&str; 4] = ["toml", "tmx", "tmj", "png"];
+
+// Campaña: lista ordenada de archivos de nivel (.toml o .txt)
+pub struct LevelPack {
+    pub name: String,
+    pub levels: Vec<PathBuf>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PackManifest {
+    name: Option<String>,
+    levels: Vec<String>,
+}
+
+// Carga un pack desde una carpeta. Con pack.toml se usa el orden del manifiesto;
+// si no, los .toml/.tmx/.tmj/.png de la carpeta en orden natural (nivel2 antes que nivel10),
+// o los .txt si no hay ninguno de esos. Las paletas y lo que nombra otro archivo de la
+// carpeta (tablas de casillas, pisos, texturas...) no cuentan como niveles.
+pub fn load_pack(dir: &Path) -> Result<LevelPack, String> {
+    let dir_name = dir.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    let manifest_path = dir.join(MANIFEST);
+
+    if manifest_path.exists() {
+        let text = fs::read_to_string(&manifest_path)
+            .map_err(|e| format!("{}: {}", manifest_path.display(), e))?;
+        let manifest: PackManifest = toml::from_str(&text)
+            .map_err(|e| format!("{}: {}", manifest_path.display(), e))?;
+        if manifest.levels.is_empty() {
+            return Err(format!("{}: el pack no tiene niveles", manifest_path.display()));
+        }
+        return Ok(LevelPack {
+            name: manifest.name.unwrap_or(dir_name),
+            levels: manifest.levels.iter().map(|l| dir.join(l)).collect(),
+        });
+    }
+
+    let entries = fs::read_dir(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    let files: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.is_file())
+        .collect();
+
+    let referenced = referenced_files(&files);
+    let with_extension = |exts: &[&str]| -> Vec<PathBuf> {
+        files.iter()
+            .filter(|p| extension(p).is_some_and(|e| exts.contains(&e)))
+            .filter(|p| !referenced.contains(&canonical(p)))
+            .cloned()
+            .collect()
+    };
+    let mut levels: Vec<PathBuf> = with_extension(&LEVEL_EXTENSIONS)
+        .into_iter()
+        .filter(|p| p.file_name().and_then(|n| n.to_str()).is_some_and(|n| n != MANIFEST && !n.ends_with("palette.toml")))
+        .collect();
+    if levels.is_empty() {
+        levels = with_extension(&["txt"]);
+    }
+    if levels.is_empty() {
+        return Err(format!("{}: no hay niveles", dir.display()));
+    }
+    levels.sort_by(|a, b| natural_cmp(&a.to_string_lossy(), &b.to_string_lossy()));
+
+    Ok(LevelPack { name: dir_name, levels })
+}
+
+fn extension(path: &Path) -> Option<&str> {
+    path.extension().and_then(|e| e.to_str())
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+// Archivos que nombran los de `files`, y los que nombran esos (un mapa de Tiled y sus
+// tilesets, por ejemplo). Cualquier texto del archivo que sea la ruta de un archivo cuenta.
+fn referenced_files(files: &[PathBuf]) -> HashSet<PathBuf> {
+    let mut referenced = HashSet::new();
+    let mut pending = files.to_vec();
+    while let Some(file) = pending.pop() {
+        for name in file_strings(&file) {
+            let path = file.parent().unwrap_or(Path::new(".")).join(name);
+            if path.is_file() && referenced.insert(canonical(&path)) {
+                pending.push(path);
+            }
+        }
+    }
+    referenced
+}
+
+// Textos de un archivo de nivel, tabla de casillas o tileset; nada para el resto
+fn file_strings(path: &Path) -> Vec<String> {
+    let mut strings = Vec::new();
+    let Some(ext) = extension(path).filter(|e| ["toml", "tmx", "tmj", "tsx", "tsj"].contains(e)) else {
+        return strings;
+    };
+    let Ok(text) = fs::read_to_string(path) else { return strings };
+    match ext {
+        "toml" => {
+            if let Ok(table) = toml::from_str::<toml::Table>(&text) {
+                let mut stack: Vec<toml::Value> = table.into_iter().map(|(_, v)| v).collect();
+                while let Some(value) = stack.pop() {
+                    match value {
+                        toml::Value::String(s) => strings.push(s),
+                        toml::Value::Array(items) => stack.extend(items),
+                        toml::Value::Table(table) => stack.extend(table.into_iter().map(|(_, v)| v)),
+                        _ => {}
+                    }
+                }
+            }
+        }
+        "tmj" | "tsj" => {
+            if let Ok(value) = serde_json::from_str::<serde_json::Value>(&text) {
+                let mut stack = vec![value];
+                while let Some(value) = stack.pop() {
+                    match value {
+                        serde_json::Value::String(s) => strings.push(s),
+                        serde_json::Value::Array(items) => stack.extend(items),
+                        serde_json::Value::Object(map) => stack.extend(map.into_iter().map(|(_, v)| v)),
+                        _ => {}
+                    }
+                }
+            }
+        }
+        _ => {
+            if let Ok(doc) = roxmltree::Document::parse(&text) {
+                strings.extend(doc.descendants().flat_map(|n| n.attributes()).map(|a| a.value().to_string()));
+            }
+        }
+    }
+    strings
+}
+
+// Busca packs en `root` y en cada subcarpeta directa. Las carpetas sin niveles se ignoran.
+pub fn discover_packs(root: &Path) -> Vec<LevelPack> {
+    let mut dirs = vec![root.to_path_buf()];
+    if let Ok(entries) = fs::read_dir(root) {
+        let mut subdirs: Vec<PathBuf> = entries
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.is_dir())
+            .collect();
+        subdirs.sort_by(|a, b| natural_cmp(&a.to_string_lossy(), &b.to_string_lossy()));
+        dirs.extend(subdirs);
+    }
+
+    let mut packs = Vec::new();
+    for dir in dirs {
+        match load_pack(&dir) {
+            Ok(pack) => packs.push(pack),
+            // Un manifiesto roto sí se avisa; una carpeta sin niveles no
+            Err(e) if dir.join(MANIFEST).exists() => eprintln!("{}", e),
+            Err(_) => {}
+        }
+    }
+    packs
+}
+
+// Compara cadenas tratando las secuencias de dígitos como números
+fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a = a.chars().peekable();
+    let mut b = b.chars().peekable();
+
+    loop {
+        match (a.peek().copied(), b.peek().copied()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(ca), Some(cb)) if ca.is_ascii_digit() && cb.is_ascii_digit() => {
+                let mut na = String::new();
+                while let Some(c) = a.next_if(|c| c.is_ascii_digit()) {
+                    na.push(c);
+                }
+                let mut nb = String::new();
+                while let Some(c) = b.next_if(|c| c.is_ascii_digit()) {
+                    nb.push(c);
+                }
+                // Sin ceros a la izquierda, el número más largo es el mayor
+                let (ta, tb) = (na.trim_start_matches('0'), nb.trim_start_matches('0'));
+                let ord = ta.len().cmp(&tb.len()).then_with(|| ta.cmp(tb));
+                if ord != Ordering::Equal {
+                    return ord;
+                }
+            }
+            (Some(ca), Some(cb)) => {
+                if ca != cb {
+                    return ca.cmp(&cb);
+                }
+                a.next();
+                b.next();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn natural_order() {
+        let mut names = vec!["nivel10", "nivel2", "nivel1", "a"];
+        names.sort_by(|a, b| natural_cmp(a, b));
+        assert_eq!(names, ["a", "nivel1", "nivel2", "nivel10"]);
+    }
+
+    #[test]
+    fn discovery_skips_palettes_and_referenced_files() {
+        let dir = std::env::temp_dir().join(format!("pack_test_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let files = [
+            ("a.toml", "maze = \"a.txt\"\ntiles = \"mis_tiles.toml\"\n"),
+            ("a.txt", "111\n1 1\n111\n"),
+            ("mis_tiles.toml", "[[tile]]\nid = 1\nglyph = \"1\"\ntexture = \"pared.png\"\n"),
+            ("pared.png", ""),
+            ("b.png", ""),
+            ("b.palette.toml", "\"#ffffff\" = 0\n"),
+            ("palette.toml", "\"#000000\" = 1\n"),
+            ("c.tmx", "<map><tileset firstgid=\"1\" source=\"t.tsx\"/></map>"),
+            ("t.tsx", "<tileset><image source=\"t.png\"/></tileset>"),
+            ("t.png", ""),
+        ];
+        for (name, text) in files {
+            fs::write(dir.join(name), text).unwrap();
+        }
+
+        let pack = load_pack(&dir);
+        fs::remove_dir_all(&dir).unwrap();
+        let names: Vec<String> = pack.unwrap().levels.iter()
+            .map(|p| p.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(names, ["a.toml", "b.png", "c.tmx"]);
+    }
+}
@@ -0,0 +1,425 @@
+// pathfinding.rs
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashSet, VecDeque};
+use crate::maze::Maze;
+
+// Casilla del mapa (x, y)
+pub type Cell = (i32, i32);
+
+// Cada casilla se divide en cuatro triángulos que se juntan en el centro (norte, este, sur, oeste),
+// así una pared fina o diagonal corta el paso solo entre los triángulos que separa.
+// Un nodo de la búsqueda es (casilla, triángulo).
+type Node = (Cell, usize);
+
+// Hacia qué vecina da cada triángulo y dónde queda su centro dentro de la casilla
+const QUADRANTS: [(Cell, (f64, f64)); 4] = [
+    ((0, -1), (0.5, 1.0 / 6.0)),
+    ((1, 0), (5.0 / 6.0, 0.5)),
+    ((0, 1), (0.5, 5.0 / 6.0)),
+    ((-1, 0), (1.0 / 6.0, 0.5)),
+];
+
+fn center(((x, y), q): Node) -> (f64, f64) {
+    let (cx, cy) = QUADRANTS[q].1;
+    (x as f64 + cx, y as f64 + cy)
+}
+
+fn cell_nodes(cell: Cell) -> [Node; 4] {
+    [(cell, 0), (cell, 1), (cell, 2), (cell, 3)]
+}
+
+// Pasos desde un triángulo, con la misma regla que Player::is_wall: a los dos de al lado en la
+// misma casilla (costo 0) y al de enfrente en la casilla vecina (costo 1), sin cruzar paredes
+// finas, sin entrar a paredes ni a casillas en que `passable` da false (puertas cerradas)
+// y sin escalones más altos que maze::MAX_STEP
+fn steps(maze: &Maze, node: Node, passable: &impl Fn(Cell) -> bool) -> Vec<(Node, usize)> {
+    let (cell, q) = node;
+    let mut out = Vec::new();
+    for side in [(q + 1) % 4, (q + 3) % 4] {
+        let next = (cell, side);
+        if !maze.crosses_wall(center(node), center(next)) {
+            out.push((next, 0));
+        }
+    }
+    let ((dx, dy), _) = QUADRANTS[q];
+    let to = (cell.0 + dx, cell.1 + dy);
+    let next = (to, (q + 2) % 4);
+    if !maze.is_solid(to.0, to.1) && passable(to) && maze.can_step(cell, to) && !maze.crosses_wall(center(node), center(next)) {
+        out.push((next, 1));
+    }
+    out
+}
+
+fn index(maze: &Maze, (x, y): Cell) -> usize {
+    y as usize * maze.width() + x as usize
+}
+
+fn node_index(maze: &Maze, (cell, q): Node) -> usize {
+    index(maze, cell) * 4 + q
+}
+
+// Reconstruye el camino siguiendo los padres desde `goal`, una vez por casilla
+fn rebuild_path(maze: &Maze, came_from: &[Option<Node>], goal: Node) -> Vec<Cell> {
+    let mut path = vec![goal.0];
+    let mut current = goal;
+    while let Some(previous) = came_from[node_index(maze, current)] {
+        if path.last() != Some(&previous.0) {
+            path.push(previous.0);
+        }
+        current = previous;
+    }
+    path.reverse();
+    path
+}
+
+// Búsqueda en anchura con pasos de costo 0 y 1 (cola doble) desde los triángulos de `start`.
+// `link(casilla)` agrega un salto de costo 1 a otra casilla del mismo piso (teleportadores).
+// Devuelve los pasos hasta cada nodo y desde qué nodo se llegó.
+fn search(
+    maze: &Maze,
+    start: Cell,
+    link: impl Fn(Cell) -> Option<Cell>,
+    passable: impl Fn(Cell) -> bool,
+) -> (Vec<Option<usize>>, Vec<Option<Node>>) {
+    let size = maze.width() * maze.height() * 4;
+    let mut dist = vec![None; size];
+    let mut came_from = vec![None; size];
+    if maze.is_solid(start.0, start.1) {
+        return (dist, came_from);
+    }
+
+    let mut queue = VecDeque::new();
+    for node in cell_nodes(start) {
+        dist[node_index(maze, node)] = Some(0);
+        queue.push_back(node);
+    }
+
+    while let Some(node) = queue.pop_front() {
+        let d = dist[node_index(maze, node)].unwrap();
+        let mut next = steps(maze, node, &passable);
+        if let Some(to) = link(node.0).filter(|&(x, y)| !maze.is_solid(x, y) && passable((x, y))) {
+            next.extend(cell_nodes(to).map(|n| (n, 1)));
+        }
+        for (n, cost) in next {
+            let i = node_index(maze, n);
+            if dist[i].is_none_or(|old| d + cost < old) {
+                dist[i] = Some(d + cost);
+                came_from[i] = Some(node);
+                if cost == 0 {
+                    queue.push_front(n);
+                } else {
+                    queue.push_back(n);
+                }
+            }
+        }
+    }
+
+    (dist, came_from)
+}
+
+// Sin llaves (un laberinto suelto) las puertas comunes se abren con E y las que tienen cerradura no
+pub fn keyless(maze: &Maze, (x, y): Cell) -> bool {
+    maze.door_lock(x, y).is_none()
+}
+
+// Distancia en pasos desde `start` a cada casilla (índice y * ancho + x), entrando solo
+// a las casillas en que `passable` da true. None si la casilla no se puede alcanzar.
+pub fn distances(maze: &Maze, start: Cell, passable: impl Fn(Cell) -> bool) -> Vec<Option<usize>> {
+    let (dist, _) = search(maze, start, |_| None, passable);
+    dist.chunks(4).map(|d| d.iter().flatten().min().copied()).collect()
+}
+
+// Camino más corto con búsqueda en anchura, sin llaves (ver keyless). Incluye inicio y meta.
+pub fn bfs_path(maze: &Maze, start: Cell, goal: Cell) -> Option<Vec<Cell>> {
+    bfs_path_linked(maze, start, goal, |_| None, |cell| keyless(maze, cell))
+}
+
+// Como bfs_path, pero `link(casilla)` agrega un salto a otra casilla del mismo piso
+// (teleportadores), que cuenta como un paso más, y no entra a las casillas en que
+// `passable` da false
+pub fn bfs_path_linked(
+    maze: &Maze,
+    start: Cell,
+    goal: Cell,
+    link: impl Fn(Cell) -> Option<Cell>,
+    passable: impl Fn(Cell) -> bool,
+) -> Option<Vec<Cell>> {
+    if maze.is_solid(start.0, start.1) || maze.is_solid(goal.0, goal.1) {
+        return None;
+    }
+
+    let (dist, came_from) = search(maze, start, link, passable);
+    cell_nodes(goal)
+        .into_iter()
+        .filter_map(|node| dist[node_index(maze, node)].map(|d| (d, node)))
+        .min()
+        .map(|(_, node)| rebuild_path(maze, &came_from, node))
+}
+
+// Camino más corto con A* (heurística Manhattan) por las casillas en que `passable` da true.
+// Mismo resultado que bfs_path_linked sin saltos en longitud, pero explora menos casillas
+// en mapas grandes.
+pub fn astar_path(maze: &Maze, start: Cell, goal: Cell, passable: impl Fn(Cell) -> bool) -> Option<Vec<Cell>> {
+    if maze.is_solid(start.0, start.1) || maze.is_solid(goal.0, goal.1) {
+        return None;
+    }
+
+    let heuristic = |((x, y), _): Node| ((x - goal.0).abs() + (y - goal.1).abs()) as usize;
+    let size = maze.width() * maze.height() * 4;
+    let mut came_from = vec![None; size];
+    let mut cost = vec![usize::MAX; size];
+    let mut open = BinaryHeap::new();
+
+    for node in cell_nodes(start) {
+        cost[node_index(maze, node)] = 0;
+        open.push(Reverse((heuristic(node), node)));
+    }
+
+    while let Some(Reverse((_, node))) = open.pop() {
+        if node.0 == goal {
+            return Some(rebuild_path(maze, &came_from, node));
+        }
+        let current = cost[node_index(maze, node)];
+        for (next, step) in steps(maze, node, &passable) {
+            let i = node_index(maze, next);
+            if current + step < cost[i] {
+                cost[i] = current + step;
+                came_from[i] = Some(node);
+                open.push(Reverse((current + step + heuristic(next), next)));
+            }
+        }
+    }
+
+    None
+}
+
+// Número de pasos de un camino (casillas - 1)
+pub fn path_length(path: &[Cell]) -> usize {
+    path.len().saturating_sub(1)
+}
+
+// Todas las casillas alcanzables caminando desde `start`
+pub fn reachable(maze: &Maze, start: Cell) -> HashSet<Cell> {
+    let width = maze.width();
+    distances(maze, start, |_| true)
+        .iter()
+        .enumerate()
+        .filter(|(_, d)| d.is_some())
+        .map(|(i, _)| ((i % width) as i32, (i / width) as i32))
+        .collect()
+}
+
+// Alcanzables en un nivel de varios pisos: se camina dentro de cada piso y
+// `link(piso, casilla)` dice a qué (piso, casilla) lleva una escalera, ascensor o teleportador.
+// No se entra a las casillas en que `closed(piso, casilla)` da true (puertas con llave que falta).
+pub fn reachable_floors(
+    floors: &[Maze],
+    start: (usize, Cell),
+    link: impl Fn(usize, Cell) -> Option<(usize, Cell)>,
+    closed: impl Fn(usize, Cell) -> bool,
+) -> HashSet<(usize, Cell)> {
+    let mut seen: HashSet<(usize, Node)> = cell_nodes(start.1).into_iter().map(|n| (start.0, n)).collect();
+    let mut queue: VecDeque<(usize, Node)> = seen.iter().copied().collect();
+    while let Some((floor, node)) = queue.pop_front() {
+        let passable = |cell| !closed(floor, cell);
+        let mut next: Vec<(usize, Node)> = steps(&floors[floor], node, &passable).into_iter().map(|(n, _)| (floor, n)).collect();
+        if let Some((target, to)) = link(floor, node.0)
+            && floors.get(target).is_some_and(|maze| !maze.is_solid(to.0, to.1))
+            && !closed(target, to)
+        {
+            next.extend(cell_nodes(to).map(|n| (target, n)));
+        }
+        for step in next {
+            if seen.insert(step) {
+                queue.push_back(step);
+            }
+        }
+    }
+    seen.into_iter().map(|(floor, (cell, _))| (floor, cell)).collect()
+}
+
+pub fn find_exit(maze: &Maze) -> Option<Cell> {
+    (0..maze.height() as i32)
+        .flat_map(|y| (0..maze.width() as i32).map(move |x| (x, y)))
+        .find(|&(x, y)| maze.is_exit(x, y))
+}
+
+// Pasos hasta la salida, None si no hay salida o no se alcanza
+pub fn distance_to_exit(maze: &Maze, start: Cell) -> Option<usize> {
+    let exit = find_exit(maze)?;
+    bfs_path(maze, start, exit).map(|path| path_length(&path))
+}
+
+// Casilla libre más cercana a `from` (en pasos, atravesando paredes).
+// Sirve para sacar al jugador de una pared si el mapa cambia bajo sus pies.
+pub fn nearest_open_cell(maze: &Maze, from: Cell) -> Option<Cell> {
+    if maze.width() == 0 || maze.height() == 0 {
+        return None;
+    }
+    let in_bounds = |(x, y): Cell| x >= 0 && y >= 0 && (x as usize) < maze.width() && (y as usize) < maze.height();
+    let start = (
+        from.0.clamp(0, maze.width() as i32 - 1),
+        from.1.clamp(0, maze.height() as i32 - 1),
+    );
+
+    let mut visited = vec![false; maze.width() * maze.height()];
+    let mut queue = VecDeque::new();
+    visited[index(maze, start)] = true;
+    queue.push_back(start);
+
+    while let Some(cell) = queue.pop_front() {
+        if !maze.is_solid(cell.0, cell.1) {
+            return Some(cell);
+        }
+        let (x, y) = cell;
+        for next in [(x + 1, y), (x - 1, y), (x, y + 1), (x, y - 1)] {
+            if in_bounds(next) && !visited[index(maze, next)] {
+                visited[index(maze, next)] = true;
+                queue.push_back(next);
+            }
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::{generate, GeneratorConfig};
+    use crate::maze::parse_maze;
+    use crate::tiles::TileSet;
+
+    fn maze(text: &str) -> Maze {
+        let (maze, errors) = parse_maze(text, TileSet::builtin());
+        assert!(errors.is_empty());
+        maze
+    }
+
+    // La búsqueda de antes de los triángulos: casilla por casilla, en las cuatro direcciones
+    fn grid_distances(maze: &Maze, start: Cell) -> Vec<Option<usize>> {
+        let mut dist = vec![None; maze.width() * maze.height()];
+        let mut queue = VecDeque::from([start]);
+        dist[index(maze, start)] = Some(0);
+        while let Some((x, y)) = queue.pop_front() {
+            let d = dist[index(maze, (x, y))].unwrap();
+            for next in [(x + 1, y), (x - 1, y), (x, y + 1), (x, y - 1)] {
+                if !maze.is_solid(next.0, next.1) && maze.can_step((x, y), next) && dist[index(maze, next)].is_none() {
+                    dist[index(maze, next)] = Some(d + 1);
+                    queue.push_back(next);
+                }
+            }
+        }
+        dist
+    }
+
+    fn open_cells(maze: &Maze) -> Vec<Cell> {
+        (0..maze.height() as i32)
+            .flat_map(|y| (0..maze.width() as i32).map(move |x| (x, y)))
+            .filter(|&(x, y)| !maze.is_solid(x, y))
+            .collect()
+    }
+
+    // Un camino válido: empieza y termina donde debe y avanza de a una casilla vecina libre
+    fn assert_walkable(maze: &Maze, path: &[Cell], start: Cell, goal: Cell) {
+        assert_eq!(path.first(), Some(&start));
+        assert_eq!(path.last(), Some(&goal));
+        for pair in path.windows(2) {
+            let ((x0, y0), (x1, y1)) = (pair[0], pair[1]);
+            assert_eq!((x0 - x1).abs() + (y0 - y1).abs(), 1);
+            assert!(!maze.is_solid(x1, y1));
+        }
+    }
+
+    #[test]
+    fn plain_grid_matches_cell_search() {
+        let mut mazes = vec![maze("11111111\n10000101\n10110001\n10_00111\n10011001\n11000051\n11111111\n")];
+        for seed in 1..6 {
+            mazes.push(generate(&GeneratorConfig { width: 11, height: 11, seed, loops: 0.2, ..GeneratorConfig::default() }));
+        }
+
+        for maze in &mazes {
+            let start = open_cells(maze)[0];
+            let expected = grid_distances(maze, start);
+            assert_eq!(distances(maze, start, |_| true), expected);
+            for goal in open_cells(maze) {
+                let steps = expected[index(maze, goal)];
+                let path = bfs_path(maze, start, goal);
+                assert_eq!(path.as_deref().map(path_length), steps);
+                if let Some(path) = path {
+                    assert_walkable(maze, &path, start, goal);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn diagonal_cuts_off_the_blocked_side() {
+        // `/` une oeste con norte y sur con este; `\` une oeste con sur
+        let slash = maze("11111\n10/11\n11011\n11111\n");
+        assert!(bfs_path(&slash, (1, 1), (2, 2)).is_none());
+        assert!(astar_path(&slash, (1, 1), (2, 2), |_| true).is_none());
+        assert!(reachable(&slash, (1, 1)).contains(&(2, 1)));
+        assert!(!reachable(&slash, (1, 1)).contains(&(2, 2)));
+
+        let backslash = maze("11111\n10\\11\n11011\n11111\n");
+        assert_eq!(bfs_path(&backslash, (1, 1), (2, 2)), Some(vec![(1, 1), (2, 1), (2, 2)]));
+
+        // Ninguna de las dos deja cruzar de oeste a este
+        for glyph in ['/', '\\'] {
+            let corridor = maze(&format!("11111\n10{}01\n11111\n", glyph));
+            assert!(bfs_path(&corridor, (1, 1), (3, 1)).is_none());
+        }
+
+        // Una pared fina en el borde de arriba corta solo ese lado
+        let thin = maze("1111\n1001\n10-1\n1111\n");
+        assert_eq!(bfs_path(&thin, (2, 1), (2, 2)).as_deref().map(path_length), Some(3));
+    }
+
+    #[test]
+    fn astar_agrees_with_bfs() {
+        let mut mazes = vec![maze("111111111\n100/00\\01\n10-0|0001\n10\\0_0/01\n100000051\n111111111\n")];
+        for seed in 10..14 {
+            mazes.push(generate(&GeneratorConfig { width: 11, height: 11, seed, loops: 0.3, ..GeneratorConfig::default() }));
+        }
+
+        for maze in &mazes {
+            let cells = open_cells(maze);
+            for &start in cells.iter().step_by(3) {
+                for &goal in &cells {
+                    let bfs = bfs_path(maze, start, goal);
+                    let astar = astar_path(maze, start, goal, |_| true);
+                    assert_eq!(bfs.as_deref().map(path_length), astar.as_deref().map(path_length), "{:?} -> {:?}", start, goal);
+                    if let Some(path) = astar {
+                        assert_walkable(maze, &path, start, goal);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn doors_follow_movement_rules() {
+        // Por la puerta son 4 pasos; dando la vuelta por abajo, 8
+        let text = |door: char| format!("1111111\n10{}0001\n1011101\n1000001\n1111111\n", door);
+        let steps = |path: Option<Vec<Cell>>| path.as_deref().map(path_length);
+
+        let locked = maze(&text('r'));
+        assert_eq!(steps(bfs_path(&locked, (1, 1), (5, 1))), Some(8));
+        assert_eq!(steps(bfs_path_linked(&locked, (1, 1), (5, 1), |_| None, |_| true)), Some(4));
+        let sealed = maze("1111111\n10r0001\n1111111\n");
+        assert!(bfs_path(&sealed, (1, 1), (5, 1)).is_none());
+        assert_eq!(distances(&sealed, (1, 1), |cell| keyless(&sealed, cell))[index(&sealed, (3, 1))], None);
+
+        // Una puerta común se rodea mientras está cerrada y se cruza cuando se abre
+        let mut door = maze(&text('D'));
+        fn open(maze: &Maze) -> impl Fn(Cell) -> bool + '_ {
+            move |(x, y)| !maze.blocks(x, y)
+        }
+        assert_eq!(steps(astar_path(&door, (1, 1), (5, 1), open(&door))), Some(8));
+        assert_eq!(steps(bfs_path_linked(&door, (1, 1), (5, 1), |_| None, open(&door))), Some(8));
+        assert!(door.use_door(2, 1));
+        door.update(10.0, (1, 1));
+        assert_eq!(steps(astar_path(&door, (1, 1), (5, 1), open(&door))), Some(4));
+    }
+}
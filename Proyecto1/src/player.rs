@@ -1,23 +1,33 @@
 // player.rs
 use sdl2::keyboard::Keycode;
-use crate::maze;
+use crate::keys::Inventory;
+use crate::maze::Maze;
+
+// Qué tan rápido la cámara sigue la altura del piso al subir o bajar escalones
+const STEP_SMOOTHING: f64 = 10.0;
+// Distancia mínima a una pared fina, para que la cámara no la atraviese
+const WALL_MARGIN: f64 = 0.15;
 
 pub struct Player {
     pub x: f64,
     pub y: f64,
+    pub z: f64,          // altura del piso bajo los pies (suavizada)
     pub dir_angle: f64,
+    pub inventory: Inventory,
 }
 
 impl Player {
-    pub fn new() -> Self {
+    pub fn new(x: f64, y: f64, dir_angle: f64) -> Self {
         Player {
-            x: 1.5,
-            y: 1.5,
-            dir_angle: 0.0,
+            x,
+            y,
+            z: 0.0,
+            dir_angle,
+            inventory: Inventory::new(),
         }
     }
     
-    pub fn update_position(&mut self, keys: &[Keycode], delta_time: f64) {
+    pub fn update_position(&mut self, maze: &Maze, keys: &[Keycode], delta_time: f64) {
         let move_speed = 3.0 * delta_time;
         let mut new_x = self.x;
         let mut new_y = self.y;
@@ -45,12 +55,15 @@ impl Player {
         }
         
         // Collision detection
-        if !self.is_wall(new_x, self.y) {
+        if !self.is_wall(maze, new_x, self.y) {
             self.x = new_x;
         }
-        if !self.is_wall(self.x, new_y) {
+        if !self.is_wall(maze, self.x, new_y) {
             self.y = new_y;
         }
+
+        let ground = maze.elevation_at(self.x, self.y);
+        self.z += (ground - self.z) * (STEP_SMOOTHING * delta_time).min(1.0);
     }
     
     pub fn rotate(&mut self, mouse_delta: i32) {
@@ -58,16 +71,37 @@ impl Player {
         self.dir_angle += mouse_delta as f64 * rotation_speed;
     }
     
-    fn is_wall(&self, x: f64, y: f64) -> bool {
-        let map_x = x as usize;
-        let map_y = y as usize;
-        
-        if map_x >= maze::MAP_WIDTH || map_y >= maze::MAP_HEIGHT {
-            return true;
+    // true si la puerta de la casilla tiene cerradura y el jugador no lleva la llave
+    pub fn is_locked_out(&self, maze: &Maze, x: i32, y: i32) -> bool {
+        maze.door_lock(x, y).is_some_and(|color| !self.inventory.has_key(color))
+    }
+
+    // Dirección de la vista redondeada al eje más cercano (para empujar paredes)
+    pub fn facing_dir(&self) -> (i32, i32) {
+        let (dx, dy) = (self.dir_angle.cos(), self.dir_angle.sin());
+        if dx.abs() >= dy.abs() {
+            (dx.signum() as i32, 0)
+        } else {
+            (0, dy.signum() as i32)
         }
-        
-        unsafe {
-            maze::MAP[map_y][map_x] != 0 && maze::MAP[map_y][map_x] != 5
+    }
+
+    // Casilla justo enfrente del jugador (para usar puertas)
+    pub fn facing_cell(&self) -> (i32, i32) {
+        let x = self.x + self.dir_angle.cos();
+        let y = self.y + self.dir_angle.sin();
+        (x.floor() as i32, y.floor() as i32)
+    }
+    
+    fn is_wall(&self, maze: &Maze, x: f64, y: f64) -> bool {
+        let from = (self.x.floor() as i32, self.y.floor() as i32);
+        let to = (x.floor() as i32, y.floor() as i32);
+        if maze.blocks_at(x, y) || self.is_locked_out(maze, to.0, to.1) || !maze.can_step(from, to) {
+            return true;
         }
+
+        // Paredes finas: no cruzarlas ni acercarse de más (alejarse sí, aunque ya esté cerca)
+        let near = maze.wall_distance((x, y));
+        maze.crosses_wall((self.x, self.y), (x, y)) || (near < WALL_MARGIN && near < maze.wall_distance((self.x, self.y)))
     }
 }
\ No newline at end of file
@@ -0,0 +1,47 @@
+// pushwall.rs
+// Paredes secretas: al usarlas se deslizan hasta dos casillas hacia atrás
+// y dejan ver lo que escondían.
+
+const PUSH_SPEED: f64 = 1.25;   // casillas por segundo
+pub const PUSH_DISTANCE: i32 = 2;
+
+#[derive(Clone)]
+pub struct PushWall {
+    pub from: (i32, i32),
+    pub dir: (i32, i32),     // un eje: (±1, 0) o (0, ±1)
+    pub tile: u8,
+    pub distance: i32,       // casillas libres que va a recorrer (1 o 2)
+    pub progress: f64,       // casillas recorridas hasta ahora
+}
+
+impl PushWall {
+    pub fn new(from: (i32, i32), dir: (i32, i32), tile: u8, distance: i32) -> Self {
+        PushWall { from, dir, tile, distance, progress: 0.0 }
+    }
+
+    // true cuando llegó al final
+    pub fn update(&mut self, delta_time: f64) -> bool {
+        self.progress = (self.progress + PUSH_SPEED * delta_time).min(self.distance as f64);
+        self.progress >= self.distance as f64
+    }
+
+    // Esquina superior izquierda del bloque en coordenadas del mundo
+    pub fn position(&self) -> (f64, f64) {
+        (
+            self.from.0 as f64 + self.dir.0 as f64 * self.progress,
+            self.from.1 as f64 + self.dir.1 as f64 * self.progress,
+        )
+    }
+
+    // Casilla donde queda al terminar
+    pub fn target(&self) -> (i32, i32) {
+        (self.from.0 + self.dir.0 * self.distance, self.from.1 + self.dir.1 * self.distance)
+    }
+
+    // Si el bloque ocupa (aunque sea en parte) la casilla
+    pub fn covers(&self, x: i32, y: i32) -> bool {
+        let (bx, by) = self.position();
+        let overlaps = |cell: i32, start: f64| (cell as f64) < start + 1.0 && start < (cell + 1) as f64;
+        overlaps(x, bx) && overlaps(y, by)
+    }
+}
@@ -0,0 +1,111 @@
+// segment.rs
+// Paredes finas y diagonales: segmentos dentro de una casilla, en coordenadas
+// locales (0..1) en la tabla de casillas y del mundo una vez ubicados en el mapa.
+
+pub type Point = (f64, f64);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Segment {
+    pub a: Point,
+    pub b: Point,
+}
+
+fn cross(u: Point, v: Point) -> f64 {
+    u.0 * v.1 - u.1 * v.0
+}
+
+fn sub(p: Point, q: Point) -> Point {
+    (p.0 - q.0, p.1 - q.1)
+}
+
+impl Segment {
+    pub fn new(a: Point, b: Point) -> Self {
+        Segment { a, b }
+    }
+
+    // El mismo segmento movido a la casilla (x, y)
+    pub fn offset(&self, x: i32, y: i32) -> Segment {
+        let (x, y) = (x as f64, y as f64);
+        Segment::new((self.a.0 + x, self.a.1 + y), (self.b.0 + x, self.b.1 + y))
+    }
+
+    pub fn length(&self) -> f64 {
+        let (dx, dy) = sub(self.b, self.a);
+        (dx * dx + dy * dy).sqrt()
+    }
+
+    // Distancia a lo largo del rayo y posición sobre el segmento (0 en `a`, 1 en `b`)
+    pub fn ray_hit(&self, origin: Point, dir: Point) -> Option<(f64, f64)> {
+        let edge = sub(self.b, self.a);
+        let denom = cross(dir, edge);
+        if denom.abs() < 1e-12 {
+            return None;
+        }
+        let to_a = sub(self.a, origin);
+        let t = cross(to_a, edge) / denom;
+        let u = cross(to_a, dir) / denom;
+        (t > 1e-9 && (0.0..=1.0).contains(&u)).then_some((t, u))
+    }
+
+    // Si el tramo p-q atraviesa el segmento (tocarlo en una punta no cuenta)
+    pub fn crosses(&self, p: Point, q: Point) -> bool {
+        let side = |o: Point, s: Point, e: Point| cross(sub(e, o), sub(s, o));
+        let (d1, d2) = (side(self.a, self.b, p), side(self.a, self.b, q));
+        let (d3, d4) = (side(p, q, self.a), side(p, q, self.b));
+        d1 * d2 < 0.0 && d3 * d4 < 0.0
+    }
+
+    // Distancia más corta de un punto al segmento
+    pub fn distance_to(&self, p: Point) -> f64 {
+        let edge = sub(self.b, self.a);
+        let len2 = edge.0 * edge.0 + edge.1 * edge.1;
+        let t = if len2 > 0.0 {
+            (((p.0 - self.a.0) * edge.0 + (p.1 - self.a.1) * edge.1) / len2).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        let (dx, dy) = sub(p, (self.a.0 + edge.0 * t, self.a.1 + edge.1 * t));
+        (dx * dx + dy * dy).sqrt()
+    }
+
+    // Si el segmento es más horizontal que vertical (para elegir la cara que se ve)
+    pub fn is_horizontal(&self) -> bool {
+        let (dx, dy) = sub(self.b, self.a);
+        dx.abs() >= dy.abs()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ray_hit_distance_and_position() {
+        let wall = Segment::new((2.0, 0.0), (2.0, 2.0));
+        let (t, u) = wall.ray_hit((0.0, 1.5), (1.0, 0.0)).unwrap();
+        assert!((t - 2.0).abs() < 1e-9 && (u - 0.75).abs() < 1e-9);
+
+        // Las puntas cuentan
+        assert!(wall.ray_hit((0.0, 0.0), (1.0, 0.0)).is_some_and(|(_, u)| u.abs() < 1e-9));
+        assert!(wall.ray_hit((0.0, 2.0), (1.0, 0.0)).is_some_and(|(_, u)| (u - 1.0).abs() < 1e-9));
+        // Justo fuera de las puntas, detrás del origen, o paralelo: no
+        assert!(wall.ray_hit((0.0, 2.001), (1.0, 0.0)).is_none());
+        assert!(wall.ray_hit((3.0, 1.0), (1.0, 0.0)).is_none());
+        assert!(wall.ray_hit((0.0, 1.0), (0.0, 1.0)).is_none());
+        assert!(wall.ray_hit((2.0, -1.0), (0.0, 1.0)).is_none());
+    }
+
+    #[test]
+    fn crosses_ignores_touching_and_parallel() {
+        let wall = Segment::new((0.0, 0.0), (1.0, 1.0));
+        assert!(wall.crosses((0.0, 1.0), (1.0, 0.0)));
+        assert!(!wall.crosses((0.0, 1.0), (0.4, 0.6)));
+
+        // Tocar una punta o terminar sobre el segmento no es cruzarlo
+        assert!(!wall.crosses((1.0, 1.0), (2.0, 0.0)));
+        assert!(!wall.crosses((0.0, 1.0), (0.5, 0.5)));
+        // Paralelo, separado o encima del mismo segmento
+        assert!(!wall.crosses((0.0, 0.5), (1.0, 1.5)));
+        assert!(!wall.crosses((0.2, 0.2), (0.8, 0.8)));
+    }
+}
@@ -1,20 +1,27 @@
 // sprite.rs
-use sdl2::render::{WindowCanvas, Texture};
-use sdl2::rect::Rect;
-use crate::maze;
+use crate::caster::{Environment, View};
+use crate::framebuffer::{Framebuffer, Image};
+use crate::maze::Maze;
+
+// Distancia a la que el jugador recoge un objeto
+const PICKUP_RADIUS: f64 = 0.5;
+// Los sprites más cerca de la cámara que esto no se dibujan (su tamaño en pantalla se dispara)
+const NEAR_PLANE: f64 = 0.05;
 
 pub struct Sprite {
     pub x: f64,
     pub y: f64,
     pub texture_index: usize,
+    pub key: Option<String>,   // llave que se recoge al pasar por encima
+    pub floor: usize,          // piso del nivel en el que está
 }
 
-pub struct SpriteRenderer<'a> {
-    textures: Vec<Texture<'a>>,
+pub struct SpriteRenderer {
+    textures: Vec<Image>,
     sprites: Vec<Sprite>,
 }
 
-impl<'a> SpriteRenderer<'a> {
+impl SpriteRenderer {
     pub fn new() -> Self {
         SpriteRenderer {
             textures: Vec::new(),
@@ -22,7 +29,7 @@ impl<'a> SpriteRenderer<'a> {
         }
     }
     
-    pub fn add_texture(&mut self, texture: Texture<'a>) {
+    pub fn add_texture(&mut self, texture: Image) {
         self.textures.push(texture);
     }
     
@@ -30,20 +37,47 @@ impl<'a> SpriteRenderer<'a> {
         self.sprites.push(sprite);
     }
     
+    pub fn clear_sprites(&mut self) {
+        self.sprites.clear();
+    }
+
+    // Deja solo los sprites para los que `keep` da true
+    pub fn retain_sprites(&mut self, keep: impl FnMut(&Sprite) -> bool) {
+        self.sprites.retain(keep);
+    }
+
+    // Si hay algún sprite en la casilla (x, y) del piso `floor`
+    pub fn occupies(&self, x: i32, y: i32, floor: usize) -> bool {
+        self.sprites.iter().any(|s| s.floor == floor && s.x.floor() as i32 == x && s.y.floor() as i32 == y)
+    }
+
+    pub fn texture(&self, index: usize) -> Option<&Image> {
+        self.textures.get(index)
+    }
+
+    // Quita y devuelve los objetos que se pueden recoger cerca de (x, y) en el piso `floor`
+    pub fn take_pickups(&mut self, x: f64, y: f64, floor: usize) -> Vec<Sprite> {
+        let (near, rest): (Vec<Sprite>, Vec<Sprite>) = self.sprites.drain(..).partition(|s| {
+            s.key.is_some() && s.floor == floor && (s.x - x).powi(2) + (s.y - y).powi(2) <= PICKUP_RADIUS * PICKUP_RADIUS
+        });
+        self.sprites = rest;
+        near
+    }
+    
     pub fn draw_sprites(
         &self,
-        canvas: &mut WindowCanvas,
-        player_x: f64,
-        player_y: f64,
-        player_angle: f64,
-        plane_x: f64,
-        plane_y: f64,
-        screen_width: u32,
-        screen_height: u32,
-    ) -> Result<(), String> {
+        framebuffer: &mut Framebuffer,   // con la distancia de las paredes ya anotada (ver render_scene)
+        maze: &Maze,
+        floor: usize,
+        view: &View,
+        Environment { fog, lights }: Environment,
+    ) {
+        let View { x: player_x, y: player_y, z: player_z, angle: player_angle, plane_x, plane_y } = *view;
+        let (screen_width, screen_height) = (framebuffer.width, framebuffer.height);
         let mut sprite_distances: Vec<(usize, f64)> = self.sprites
             .iter()
             .enumerate()
+            .filter(|(_, s)| s.floor == floor)
             .map(|(i, s)| {
                 let dx = s.x - player_x;
                 let dy = s.y - player_y;
@@ -69,7 +103,7 @@ impl<'a> SpriteRenderer<'a> {
             let transform_x = inv_det * (dir_y * sprite_x - dir_x * sprite_y);
             let transform_y = inv_det * (-plane_y * sprite_x + plane_x * sprite_y);
             
-            if transform_y <= 0.0 {
+            if transform_y <= NEAR_PLANE {
                 continue;
             }
             
@@ -78,39 +112,72 @@ impl<'a> SpriteRenderer<'a> {
             let sprite_height = ((screen_height as f64 / transform_y).abs()) as i32;
             let sprite_width = sprite_height;
             
-            let draw_start_y = (-sprite_height / 2 + screen_height as i32 / 2).max(0);
-            let draw_end_y = (sprite_height / 2 + screen_height as i32 / 2).min(screen_height as i32);
+            // Parado sobre el piso de su casilla: se corre según la diferencia de altura con el jugador
+            let lift = ((maze.elevation_at(sprite.x, sprite.y) - player_z) * screen_height as f64 / transform_y) as i32;
+            let draw_start_y = -sprite_height / 2 + screen_height as i32 / 2 - lift;
+            let draw_start_x = -sprite_width / 2 + sprite_screen_x;
             
-            let draw_start_x = (-sprite_width / 2 + sprite_screen_x).max(0);
-            let draw_end_x = (sprite_width / 2 + sprite_screen_x).min(screen_width as i32);
+            let Some(texture) = self.textures.get(sprite.texture_index) else { continue };
+            let rows = draw_start_y.max(0)..(draw_start_y + sprite_height).min(screen_height as i32);
+            let columns = draw_start_x.max(0)..(draw_start_x + sprite_width).min(screen_width as i32);
             
-            if sprite.texture_index < self.textures.len() {
-                let texture = &self.textures[sprite.texture_index];
-                
-                let dst_rect = Rect::new(
-                    draw_start_x,
-                    draw_start_y,
-                    (draw_end_x - draw_start_x) as u32,
-                    (draw_end_y - draw_start_y) as u32,
-                );
-                
-                canvas.copy(texture, None, Some(dst_rect))?;
+            // Franja por franja, solo en los pixeles en que no hay una pared más cerca
+            for x in columns {
+                let u = (x - draw_start_x) as f64 / sprite_width as f64;
+                for y in rows.clone() {
+                    if framebuffer.depth(x, y) <= transform_y {
+                        continue;
+                    }
+                    let v = (y - draw_start_y) as f64 / sprite_height as f64;
+                    let color = texture.sample(u, v);
+                    let color = lights.map_or(color, |lights| lights.apply(color, sprite.x, sprite.y));
+                    framebuffer.draw_pixel(x, y, fog.map_or(color, |fog| fog.apply(color, transform_y)));
+                }
             }
         }
-        
-        Ok(())
     }
 }
 
-pub fn is_empty_cell(x: f64, y: f64) -> bool {
-    let map_x = x as usize;
-    let map_y = y as usize;
-    
-    if map_x >= maze::MAP_WIDTH || map_y >= maze::MAP_HEIGHT {
-        return false;
-    }
-    
-    unsafe {
-        maze::MAP[map_y][map_x] == 0
+pub fn is_empty_cell(maze: &Maze, x: f64, y: f64) -> bool {
+    maze.is_empty_at(x, y)
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::caster::{render_scene, WallTextures};
+    use crate::maze::parse_maze;
+    use crate::player::Player;
+    use crate::tiles::TileSet;
+    use sdl2::pixels::Color;
+
+    const MAGENTA: Color = Color::RGB(255, 0, 255);
+
+    #[test]
+    fn half_wall_hides_only_the_lower_rows_of_a_sprite_behind_it() {
+        // Pared de media altura en (3, 2) entre el jugador y un sprite en (5.5, 2.5)
+        let (maze, errors) = parse_maze("11111111\n10000001\n100h0001\n10000001\n11111111\n", TileSet::builtin());
+        assert!(errors.is_empty());
+        let player = Player::new(1.5, 2.5, 0.0);
+        let mut sprites = SpriteRenderer::new();
+        sprites.add_texture(Image::filled(MAGENTA));
+        sprites.add_sprite(Sprite { x: 5.5, y: 2.5, texture_index: 0, key: None, floor: 0 });
+
+        let mut framebuffer = Framebuffer::new(40, 40);
+        let walls = WallTextures::load(&TileSet::empty()).unwrap();
+        render_scene(&mut framebuffer, &maze, &player, &walls, 1.0, Environment::default());
+        sprites.draw_sprites(&mut framebuffer, &maze, 0, &View::new(&player, std::f64::consts::PI / 3.0), Environment::default());
+
+        // A distancia 4 el sprite ocupa las filas 15..25; el tope de la pared (a la altura
+        // de los ojos) cae en el horizonte, fila 20
+        let magenta = (MAGENTA.r, MAGENTA.g, MAGENTA.b);
+        for x in 16..24 {
+            for y in 15..20 {
+                assert_eq!(framebuffer.pixel(x, y), magenta, "({}, {})", x, y);
+            }
+            for y in 20..25 {
+                assert_ne!(framebuffer.pixel(x, y), magenta, "({}, {})", x, y);
+            }
+        }
+        assert_ne!(framebuffer.pixel(20, 14), magenta);
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,536 @@
+// tiled.rs
+// Importa mapas del editor Tiled (.tmx en XML, .tmj en JSON) al mismo Level
+// que producen los .toml. Capas de tiles -> códigos de pared, capas de objetos ->
+// spawn/salida/sprites, propiedades del mapa -> metadatos del nivel.
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::level::{self, Level, SpritePlacement};
+use crate::maze::{self, Maze};
+use crate::tiles::TileSet;
+
+// Bits altos del gid que Tiled usa para voltear/rotar tiles (las paredes no se pueden voltear)
+const FLIP_FLAGS: u32 = 0xF000_0000;
+
+struct Tileset {
+    first_gid: u32,
+    codes: HashMap<u32, u8>,   // id local -> propiedad "code" del tile
+}
+
+struct TiledObject {
+    kind: String,
+    x: f64,
+    y: f64,
+    properties: HashMap<String, String>,
+}
+
+struct TiledMap {
+    width: usize,
+    height: usize,
+    tile_width: f64,
+    tile_height: f64,
+    properties: HashMap<String, String>,
+    tilesets: Vec<Tileset>,
+    tileset_sources: Vec<PathBuf>,   // tilesets en archivo aparte, para la recarga en caliente
+    tile_layers: Vec<Vec<u32>>,
+    objects: Vec<TiledObject>,
+}
+
+impl TiledMap {
+    // Casilla para un gid. Sin propiedad "code", el tile con id local N es la casilla N + 1.
+    fn tile_code(&self, gid: u32, tiles: &TileSet) -> Result<u8, String> {
+        let tileset = self.tilesets
+            .iter()
+            .filter(|t| t.first_gid <= gid)
+            .max_by_key(|t| t.first_gid)
+            .ok_or_else(|| format!("tile {} sin tileset", gid))?;
+        let local = gid - tileset.first_gid;
+        let code = match tileset.codes.get(&local) {
+            Some(&code) => code as u32,
+            None => local + 1,
+        };
+        match u8::try_from(code) {
+            Ok(code) if tiles.get(code).is_some() => Ok(code),
+            _ => Err(format!("el tile {} da el código {}, que no está en la tabla de casillas", gid, code)),
+        }
+    }
+}
+
+pub fn load_tiled(path: &Path) -> Result<Level, String> {
+    let filename = path.display().to_string();
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", filename, e))?;
+    let base = path.parent().unwrap_or(Path::new("."));
+
+    let map = match path.extension().and_then(|e| e.to_str()) {
+        Some("tmx") => parse_tmx(&text, base),
+        Some("tmj") => parse_tmj(&text, base),
+        _ => Err("se esperaba un .tmx o .tmj".to_string()),
+    }
+    .map_err(|e| format!("{}: {}", filename, e))?;
+
+    let name = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    let mut level = to_level(&map, &name, base).map_err(|e| format!("{}: {}", filename, e))?;
+    level.sources.push(path.to_path_buf());
+    level.sources.extend(map.tileset_sources);
+    Ok(level)
+}
+
+fn to_level(map: &TiledMap, name: &str, base: &Path) -> Result<Level, String> {
+    let mut maze = Maze::new(map.width, map.height);
+    for layer in &map.tile_layers {
+        for (i, &gid) in layer.iter().enumerate().take(map.width * map.height) {
+            if gid & FLIP_FLAGS != 0 {
+                return Err(format!("el tile {} de ({}, {}) está volteado o rotado, y eso no se soporta",
+                    gid & !FLIP_FLAGS, i % map.width, i / map.width));
+            }
+            if gid != 0 {
+                maze.set(i % map.width, i / map.width, map.tile_code(gid, maze.tiles())?);
+            }
+        }
+    }
+
+    let mut level = Level::from_maze(name, maze);
+    let cell = |o: &TiledObject| (o.x / map.tile_width, o.y / map.tile_height);
+
+    for object in &map.objects {
+        let (x, y) = cell(object);
+        match object.kind.as_str() {
+            "spawn" => {
+                level.spawn = (x, y);
+                if let Some(facing) = object.properties.get("facing") {
+                    level.facing = parse_number(facing)?.to_radians();
+                }
+            }
+            "exit" => level.maze_mut().set(x as usize, y as usize, maze::EXIT),
+            "sprite" => {
+                let texture = match object.properties.get("texture") {
+                    Some(t) => parse_number(t)? as usize,
+                    None => 0,
+                };
+                let key = object.properties.get("key").cloned();
+                level.sprites.push(SpritePlacement { x, y, texture, key, floor: 0 });
+            }
+            _ => {}
+        }
+    }
+
+    let props = &map.properties;
+    if let Some(name) = props.get("name") {
+        level.name = name.clone();
+    }
+    level.title_card = props.get("title_card").map(|p| base.join(p));
+    level.music = props.get("music").map(|p| base.join(p));
+    if let Some(facing) = props.get("facing") {
+        level.facing = parse_number(facing)?.to_radians();
+    }
+    if let Some(color) = props.get("sky_color") {
+        level.sky_color = parse_color(color)?;
+    }
+    if let Some(color) = props.get("floor_color") {
+        level.floor_color = parse_color(color)?;
+    }
+    if let Some(shade) = props.get("side_shade") {
+        level.side_shade = parse_number(shade)?;
+    }
+
+    if level.spawn.0 < 0.0 || level.spawn.1 < 0.0 {
+        return Err("spawn fuera del mapa".to_string());
+    }
+    let errors = maze::validate_maze(level.maze(), (level.spawn.0 as usize, level.spawn.1 as usize));
+    if !errors.is_empty() {
+        return Err(maze::report_errors("mapa", &errors));
+    }
+    level::check_keys(&level)?;
+
+    Ok(level)
+}
+
+fn parse_number(value: &str) -> Result<f64, String> {
+    value.trim().parse().map_err(|_| format!("número inválido: {:?}", value))
+}
+
+// Propiedad `code` de un tile: un id de la tabla de casillas
+fn parse_code(value: &str) -> Result<u8, String> {
+    value.trim().parse().map_err(|_| format!("code inválido: {:?}, tiene que ser un id de casilla de 0 a 255", value))
+}
+
+fn parse_gid(value: &str) -> Result<u32, String> {
+    value.trim().parse().map_err(|_| format!("gid inválido: {:?}", value))
+}
+
+// Acepta colores de Tiled ("#rrggbb" o "#aarrggbb") o "r, g, b"
+fn parse_color(value: &str) -> Result<(u8, u8, u8), String> {
+    let bad = || format!("color inválido: {:?}", value);
+    if let Some(hex) = value.strip_prefix('#') {
+        let hex = if hex.len() == 8 { &hex[2..] } else { hex };
+        if hex.len() != 6 {
+            return Err(bad());
+        }
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| bad());
+        return Ok((channel(0)?, channel(2)?, channel(4)?));
+    }
+    let parts: Vec<&str> = value.split(',').map(str::trim).collect();
+    match parts.as_slice() {
+        [r, g, b] => Ok((
+            r.parse().map_err(|_| bad())?,
+            g.parse().map_err(|_| bad())?,
+            b.parse().map_err(|_| bad())?,
+        )),
+        _ => Err(bad()),
+    }
+}
+
+fn parse_csv(data: &str) -> Result<Vec<u32>, String> {
+    data.split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(parse_gid)
+        .collect()
+}
+
+// ---------- TMX (XML) ----------
+
+fn xml_attr<'a>(node: roxmltree::Node<'a, '_>, name: &str) -> Result<&'a str, String> {
+    node.attribute(name)
+        .ok_or_else(|| format!("<{}> sin atributo {}", node.tag_name().name(), name))
+}
+
+fn xml_number(node: roxmltree::Node, name: &str) -> Result<f64, String> {
+    parse_number(xml_attr(node, name)?)
+}
+
+fn xml_properties(node: roxmltree::Node) -> HashMap<String, String> {
+    let mut properties = HashMap::new();
+    for props in node.children().filter(|n| n.has_tag_name("properties")) {
+        for prop in props.children().filter(|n| n.has_tag_name("property")) {
+            if let Some(name) = prop.attribute("name") {
+                // Las propiedades de varias líneas van como texto en vez de `value`
+                let value = prop.attribute("value").or(prop.text()).unwrap_or("");
+                properties.insert(name.to_string(), value.to_string());
+            }
+        }
+    }
+    properties
+}
+
+fn xml_tileset(node: roxmltree::Node, first_gid: u32) -> Result<Tileset, String> {
+    let mut codes = HashMap::new();
+    for tile in node.children().filter(|n| n.has_tag_name("tile")) {
+        if let Some(code) = xml_properties(tile).get("code") {
+            codes.insert(xml_number(tile, "id")? as u32, parse_code(code)?);
+        }
+    }
+    Ok(Tileset { first_gid, codes })
+}
+
+fn parse_tmx(text: &str, base: &Path) -> Result<TiledMap, String> {
+    let doc = roxmltree::Document::parse(text).map_err(|e| e.to_string())?;
+    let root = doc.root_element();
+    if !root.has_tag_name("map") {
+        return Err("no es un mapa de Tiled".to_string());
+    }
+
+    let mut map = TiledMap {
+        width: xml_number(root, "width")? as usize,
+        height: xml_number(root, "height")? as usize,
+        tile_width: xml_number(root, "tilewidth")?,
+        tile_height: xml_number(root, "tileheight")?,
+        properties: xml_properties(root),
+        tilesets: Vec::new(),
+        tileset_sources: Vec::new(),
+        tile_layers: Vec::new(),
+        objects: Vec::new(),
+    };
+
+    for tileset in root.children().filter(|n| n.has_tag_name("tileset")) {
+        let first_gid = xml_number(tileset, "firstgid")? as u32;
+        map.tilesets.push(match tileset.attribute("source") {
+            Some(source) => {
+                map.tileset_sources.push(base.join(source));
+                load_external_tileset(&base.join(source), first_gid)?
+            }
+            None => xml_tileset(tileset, first_gid)?,
+        });
+    }
+
+    xml_layers(root, &mut map)?;
+    Ok(map)
+}
+
+// Recorre capas de tiles, de objetos y grupos (que pueden anidarse)
+fn xml_layers(parent: roxmltree::Node, map: &mut TiledMap) -> Result<(), String> {
+    for node in parent.children().filter(|n| n.is_element()) {
+        match node.tag_name().name() {
+            "layer" => {
+                let data = node.children().find(|n| n.has_tag_name("data"))
+                    .ok_or("capa sin <data>")?;
+                let gids = match data.attribute("encoding") {
+                    Some("csv") => parse_csv(data.text().unwrap_or(""))?,
+                    None => data.children()
+                        .filter(|n| n.has_tag_name("tile"))
+                        .map(|t| parse_gid(t.attribute("gid").unwrap_or("0")))
+                        .collect::<Result<_, _>>()?,
+                    Some(other) => return Err(format!(
+                        "codificación de capa {:?} no soportada, exporte con CSV", other
+                    )),
+                };
+                map.tile_layers.push(gids);
+            }
+            "objectgroup" => {
+                for object in node.children().filter(|n| n.has_tag_name("object")) {
+                    // Tiled 1.9+ guarda el tipo como `class`; antes era `type`
+                    let kind = ["class", "type", "name"]
+                        .iter()
+                        .filter_map(|key| object.attribute(*key))
+                        .find(|s| !s.is_empty())
+                        .unwrap_or("");
+                    map.objects.push(TiledObject {
+                        kind: kind.to_lowercase(),
+                        x: xml_number(object, "x")?,
+                        y: xml_number(object, "y")?,
+                        properties: xml_properties(object),
+                    });
+                }
+            }
+            "group" => xml_layers(node, map)?,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+// Tileset en archivo aparte (.tsx o .tsj)
+fn load_external_tileset(path: &Path, first_gid: u32) -> Result<Tileset, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    if path.extension().is_some_and(|e| e == "tsx") {
+        let doc = roxmltree::Document::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+        xml_tileset(doc.root_element(), first_gid)
+    } else {
+        let json: Value = serde_json::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+        json_tileset(&json, first_gid)
+    }
+}
+
+// ---------- TMJ (JSON) ----------
+
+fn json_number(value: &Value, key: &str) -> Result<f64, String> {
+    value.get(key).and_then(Value::as_f64).ok_or_else(|| format!("falta el campo numérico {:?}", key))
+}
+
+fn json_properties(value: &Value) -> HashMap<String, String> {
+    let mut properties = HashMap::new();
+    for prop in value.get("properties").and_then(Value::as_array).into_iter().flatten() {
+        if let Some(name) = prop.get("name").and_then(Value::as_str) {
+            let value = match prop.get("value") {
+                Some(Value::String(s)) => s.clone(),
+                Some(other) => other.to_string(),
+                None => String::new(),
+            };
+            properties.insert(name.to_string(), value);
+        }
+    }
+    properties
+}
+
+fn json_tileset(value: &Value, first_gid: u32) -> Result<Tileset, String> {
+    let mut codes = HashMap::new();
+    for tile in value.get("tiles").and_then(Value::as_array).into_iter().flatten() {
+        if let Some(code) = json_properties(tile).get("code") {
+            codes.insert(json_number(tile, "id")? as u32, parse_code(code)?);
+        }
+    }
+    Ok(Tileset { first_gid, codes })
+}
+
+fn parse_tmj(text: &str, base: &Path) -> Result<TiledMap, String> {
+    let root: Value = serde_json::from_str(text).map_err(|e| e.to_string())?;
+
+    let mut map = TiledMap {
+        width: json_number(&root, "width")? as usize,
+        height: json_number(&root, "height")? as usize,
+        tile_width: json_number(&root, "tilewidth")?,
+        tile_height: json_number(&root, "tileheight")?,
+        properties: json_properties(&root),
+        tilesets: Vec::new(),
+        tileset_sources: Vec::new(),
+        tile_layers: Vec::new(),
+        objects: Vec::new(),
+    };
+
+    for tileset in root.get("tilesets").and_then(Value::as_array).into_iter().flatten() {
+        let first_gid = json_number(tileset, "firstgid")? as u32;
+        map.tilesets.push(match tileset.get("source").and_then(Value::as_str) {
+            Some(source) => {
+                map.tileset_sources.push(base.join(source));
+                load_external_tileset(&base.join(source), first_gid)?
+            }
+            None => json_tileset(tileset, first_gid)?,
+        });
+    }
+
+    json_layers(&root, &mut map)?;
+    Ok(map)
+}
+
+fn json_layers(parent: &Value, map: &mut TiledMap) -> Result<(), String> {
+    for layer in parent.get("layers").and_then(Value::as_array).into_iter().flatten() {
+        match layer.get("type").and_then(Value::as_str) {
+            Some("tilelayer") => {
+                let gids = match layer.get("data") {
+                    Some(Value::Array(data)) => data.iter()
+                        .map(|v| v.as_u64().and_then(|gid| u32::try_from(gid).ok()).ok_or_else(|| format!("gid inválido: {}", v)))
+                        .collect::<Result<_, _>>()?,
+                    _ => return Err("capa sin `data` en arreglo, exporte con codificación CSV".to_string()),
+                };
+                map.tile_layers.push(gids);
+            }
+            Some("objectgroup") => {
+                for object in layer.get("objects").and_then(Value::as_array).into_iter().flatten() {
+                    let kind = ["class", "type", "name"]
+                        .iter()
+                        .filter_map(|key| object.get(*key).and_then(Value::as_str))
+                        .find(|s| !s.is_empty())
+                        .unwrap_or("");
+                    map.objects.push(TiledObject {
+                        kind: kind.to_lowercase(),
+                        x: json_number(object, "x")?,
+                        y: json_number(object, "y")?,
+                        properties: json_properties(object),
+                    });
+                }
+            }
+            Some("group") => json_layers(layer, map)?,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 5x4 con paredes 1 (gid 1) y una pared 4 en el borde derecho (gid 3, propiedad code)
+    const TMX: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" width="5" height="4" tilewidth="32" tileheight="32">
+ <properties>
+  <property name="name" value="Desde Tiled"/>
+ </properties>
+ <tileset firstgid="1" name="casillas" tilewidth="32" tileheight="32" tilecount="4" columns="4">
+  <tile id="2">
+   <properties>
+    <property name="code" type="int" value="{code}"/>
+   </properties>
+  </tile>
+ </tileset>
+ <layer id="1" name="paredes" width="5" height="4">
+  <data encoding="csv">
+1,1,1,1,1,
+1,0,0,0,1,
+1,0,0,0,{gid},
+1,1,1,1,1
+</data>
+ </layer>
+ <objectgroup id="2" name="objetos">
+  <object id="1" type="spawn" x="48" y="48">
+   <properties>
+    <property name="facing" type="float" value="90"/>
+   </properties>
+  </object>
+  <object id="2" type="exit" x="112" y="80"/>
+  <object id="3" type="sprite" x="80" y="48">
+   <properties>
+    <property name="texture" type="int" value="1"/>
+    <property name="key" value="red"/>
+   </properties>
+  </object>
+ </objectgroup>
+</map>
+"#;
+
+    const TMJ: &str = r#"{
+ "width": 5, "height": 4, "tilewidth": 32, "tileheight": 32,
+ "properties": [{ "name": "name", "type": "string", "value": "Desde Tiled" }],
+ "tilesets": [{
+  "firstgid": 1, "name": "casillas",
+  "tiles": [{ "id": 2, "properties": [{ "name": "code", "type": "int", "value": {code} }] }]
+ }],
+ "layers": [
+  { "type": "tilelayer", "name": "paredes", "width": 5, "height": 4,
+    "data": [1, 1, 1, 1, 1, 1, 0, 0, 0, 1, 1, 0, 0, 0, {gid}, 1, 1, 1, 1, 1] },
+  { "type": "objectgroup", "name": "objetos", "objects": [
+    { "id": 1, "type": "spawn", "x": 48, "y": 48,
+      "properties": [{ "name": "facing", "type": "float", "value": 90 }] },
+    { "id": 2, "type": "exit", "x": 112, "y": 80 },
+    { "id": 3, "type": "sprite", "x": 80, "y": 48,
+      "properties": [{ "name": "texture", "type": "int", "value": 1 },
+                     { "name": "key", "type": "string", "value": "red" }] }
+  ] }
+ ]
+}"#;
+
+    fn fixture(template: &str, code: &str, gid: &str) -> String {
+        template.replace("{code}", code).replace("{gid}", gid)
+    }
+
+    fn tmx(code: &str, gid: &str) -> Result<Level, String> {
+        to_level(&parse_tmx(&fixture(TMX, code, gid), Path::new("."))?, "t", Path::new("."))
+    }
+
+    fn tmj(code: &str, gid: &str) -> Result<Level, String> {
+        to_level(&parse_tmj(&fixture(TMJ, code, gid), Path::new("."))?, "t", Path::new("."))
+    }
+
+    fn check_fixture(level: &Level) {
+        assert_eq!(level.name, "Desde Tiled");
+        let maze = level.maze();
+        assert_eq!((maze.width(), maze.height()), (5, 4));
+        assert_eq!(maze.get(0, 0), Some(1));
+        assert_eq!(maze.get(4, 2), Some(4));
+        assert!(maze.is_empty(1, 1));
+        assert!(maze.is_exit(3, 2));
+
+        assert_eq!(level.spawn, (1.5, 1.5));
+        assert!((level.facing - 90f64.to_radians()).abs() < 1e-9);
+
+        assert_eq!(level.sprites.len(), 1);
+        let sprite = &level.sprites[0];
+        assert_eq!((sprite.x, sprite.y, sprite.texture), (2.5, 1.5, 1));
+        assert_eq!(sprite.key.as_deref(), Some("red"));
+    }
+
+    #[test]
+    fn loads_tmx_layer_and_objects() {
+        check_fixture(&tmx("4", "3").unwrap());
+    }
+
+    #[test]
+    fn loads_tmj_layer_and_objects() {
+        check_fixture(&tmj("4", "3").unwrap());
+    }
+
+    #[test]
+    fn rejects_codes_out_of_range() {
+        for result in [tmx("300", "3"), tmj("300", "3")] {
+            let error = result.err().unwrap();
+            assert!(error.contains("code inválido: \"300\""), "{}", error);
+        }
+    }
+
+    #[test]
+    fn rejects_flipped_and_oversized_gids() {
+        // gid 1 volteado en horizontal
+        for result in [tmx("4", "2147483649"), tmj("4", "2147483649")] {
+            let error = result.err().unwrap();
+            assert!(error.contains("el tile 1 de (4, 2) está volteado"), "{}", error);
+        }
+        // Sin propiedad code el tile 300 sería la casilla 301
+        for result in [tmx("4", "301"), tmj("4", "301")] {
+            let error = result.err().unwrap();
+            assert!(error.contains("el tile 301 da el código 301"), "{}", error);
+        }
+        let error = tmj("4", "4294967296").err().unwrap();
+        assert!(error.contains("gid inválido: 4294967296"), "{}", error);
+    }
+}
@@ -0,0 +1,335 @@
+// tiles.rs
+// Registro de casillas: qué significa cada valor del laberinto (sólido, textura,
+// color en el minimapa, salida...). Se carga de assets/tiles.toml y cada nivel
+// puede reemplazarlo o agregar casillas propias.
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+use crate::keys;
+use crate::segment::Segment;
+
+const BUILTIN_TILES: &str = include_str!("../assets/tiles.toml");
+// Las texturas de la tabla incluida se buscan desde la carpeta de ejecución (src/)
+const BUILTIN_BASE: &str = "../assets";
+
+const WALL_MINIMAP: [u8; 3] = [180, 180, 180];   // Pared (gris claro)
+const FLOOR_MINIMAP: [u8; 3] = [40, 40, 40];     // Vacío (oscuro)
+
+// Cara de una casilla. El norte es -y (arriba en el mapa).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Face {
+    North,
+    South,
+    East,
+    West,
+}
+
+impl Face {
+    pub const ALL: [Face; 4] = [Face::North, Face::South, Face::East, Face::West];
+
+    pub fn index(self) -> usize {
+        self as usize
+    }
+}
+
+// Casilla que lleva a otro piso del nivel, manteniendo x/y
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum FloorLink {
+    Up,     // escalera al piso de arriba
+    Down,   // escalera al piso de abajo
+    Lift,   // ascensor: al siguiente piso con ascensor en la misma casilla
+}
+
+// Texturas distintas por cara; las que falten usan `texture`
+#[derive(Deserialize, Clone, Default)]
+#[serde(deny_unknown_fields)]
+struct FaceTextures {
+    north: Option<String>,
+    south: Option<String>,
+    east: Option<String>,
+    west: Option<String>,
+}
+
+// Casilla tal como se escribe en el TOML
+#[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct TileEntry {
+    id: u8,
+    glyph: String,
+    #[serde(default)]
+    solid: bool,
+    texture: Option<String>,
+    #[serde(default)]
+    faces: FaceTextures,
+    minimap: Option<[u8; 3]>,
+    #[serde(default)]
+    exit: bool,
+    trigger: Option<String>,
+    #[serde(default)]
+    transparent: bool,
+    #[serde(default)]
+    door: bool,
+    lock: Option<String>,
+    #[serde(default)]
+    secret: bool,
+    floor_link: Option<FloorLink>,
+    #[serde(default)]
+    teleporter: bool,
+    height: Option<f64>,
+    #[serde(default)]
+    elevation: f64,
+    #[serde(default)]
+    segments: Vec<[f64; 4]>,
+    floor_texture: Option<String>,
+    ceiling_texture: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TileFile {
+    tile: Vec<TileEntry>,
+}
+
+#[derive(Clone)]
+pub struct TileDef {
+    pub id: u8,
+    pub glyphs: Vec<char>,         // el primero es el que se usa al guardar
+    pub solid: bool,               // bloquea al jugador y a los rayos
+    pub texture: Option<PathBuf>,
+    pub faces: [Option<PathBuf>; 4],   // por Face::index, reemplazan a `texture`
+    pub minimap: (u8, u8, u8),
+    pub exit: bool,
+    pub trigger: Option<String>,   // evento que se dispara al pisar la casilla
+    pub transparent: bool,         // los rayos la dibujan pero siguen de largo
+    pub door: bool,                // puerta corrediza (ver door.rs)
+    pub lock: Option<String>,      // color de la llave que abre la puerta
+    pub secret: bool,              // pared que se puede empujar (ver pushwall.rs)
+    pub floor_link: Option<FloorLink>,
+    pub teleporter: bool,          // punta de un par de teleportadores (la pareja la define el nivel)
+    pub height: f64,               // alto de la pared o puerta (1 = del piso al techo)
+    pub elevation: f64,            // altura del piso, o de la base de la pared
+    pub segments: Vec<Segment>,    // paredes finas o diagonales dentro de la casilla (0..1)
+    pub floor_texture: Option<PathBuf>,
+    pub ceiling_texture: Option<PathBuf>,   // sin techo se ve el cielo
+}
+
+#[derive(Clone)]
+pub struct TileSet {
+    tiles: Vec<Option<TileDef>>,   // indexado por id
+    glyphs: HashMap<char, u8>,
+}
+
+impl TileSet {
+    pub fn empty() -> Self {
+        TileSet {
+            tiles: vec![None; 256],
+            glyphs: HashMap::new(),
+        }
+    }
+
+    // Tabla por defecto (assets/tiles.toml, incluida en el ejecutable)
+    pub fn builtin() -> Arc<TileSet> {
+        static BUILTIN: OnceLock<Arc<TileSet>> = OnceLock::new();
+        BUILTIN
+            .get_or_init(|| {
+                let tiles = TileSet::parse(BUILTIN_TILES, Path::new(BUILTIN_BASE))
+                    .unwrap_or_else(|e| panic!("assets/tiles.toml: {}", e));
+                Arc::new(tiles)
+            })
+            .clone()
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let base = path.parent().unwrap_or(Path::new("."));
+        TileSet::parse(&text, base).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    // Las texturas se resuelven relativas a `base`
+    pub fn parse(text: &str, base: &Path) -> Result<Self, String> {
+        let file: TileFile = toml::from_str(text).map_err(|e| e.to_string())?;
+        let mut tiles = TileSet::empty();
+        tiles.merge(&file.tile, base)?;
+        Ok(tiles)
+    }
+
+    // Agrega casillas o reemplaza las que tengan el mismo id
+    pub fn merge(&mut self, entries: &[TileEntry], base: &Path) -> Result<(), String> {
+        for entry in entries {
+            let glyphs: Vec<char> = entry.glyph.chars().collect();
+            if glyphs.is_empty() {
+                return Err(format!("la casilla {} no tiene glyph", entry.id));
+            }
+            // Una puerta bloquea o no según esté abierta, no por la tabla
+            if entry.door && entry.solid {
+                return Err(format!("la casilla {} es puerta, no puede ser `solid`", entry.id));
+            }
+            if entry.secret && !entry.solid {
+                return Err(format!("la casilla {} es secreta, tiene que ser `solid`", entry.id));
+            }
+            if entry.floor_link.is_some() && (entry.solid || entry.door) {
+                return Err(format!("la casilla {} cambia de piso, no puede ser `solid` ni `door`", entry.id));
+            }
+            if entry.height.is_some() && !entry.solid && !entry.door && entry.segments.is_empty() {
+                return Err(format!("la casilla {} tiene `height` pero no es pared ni puerta", entry.id));
+            }
+            if !entry.segments.is_empty() && (entry.solid || entry.door) {
+                return Err(format!("la casilla {} tiene `segments`, no puede ser `solid` ni `door`", entry.id));
+            }
+            let segments: Vec<Segment> = entry.segments.iter()
+                .map(|&[x1, y1, x2, y2]| Segment::new((x1, y1), (x2, y2)))
+                .collect();
+            let inside = |(x, y): (f64, f64)| (0.0..=1.0).contains(&x) && (0.0..=1.0).contains(&y);
+            if segments.iter().any(|s| !inside(s.a) || !inside(s.b) || s.length() == 0.0) {
+                return Err(format!("la casilla {} tiene segmentos fuera de la casilla (0..1) o de largo 0", entry.id));
+            }
+            if entry.height.is_some_and(|h| h <= 0.0) || entry.elevation < 0.0 {
+                return Err(format!("la casilla {} tiene `height` o `elevation` fuera de rango", entry.id));
+            }
+            if entry.teleporter && (entry.solid || entry.door || entry.floor_link.is_some()) {
+                return Err(format!("la casilla {} es teleportador, no puede ser `solid`, `door` ni `floor_link`", entry.id));
+            }
+            if let Some(lock) = &entry.lock {
+                if !entry.door {
+                    return Err(format!("la casilla {} tiene `lock` pero no es puerta", entry.id));
+                }
+                keys::check_key_color(lock).map_err(|e| format!("casilla {}: {}", entry.id, e))?;
+            }
+
+            // Los caracteres de la definición anterior con este id quedan libres
+            self.glyphs.retain(|_, id| *id != entry.id);
+            for &glyph in &glyphs {
+                if let Some(other) = self.glyphs.insert(glyph, entry.id) {
+                    return Err(format!("el carácter {:?} se usa en las casillas {} y {}", glyph, other, entry.id));
+                }
+            }
+
+            let face = |f: &Option<String>| f.as_deref().map(|t| base.join(t));
+            let faces = &entry.faces;
+            let minimap = entry.minimap
+                .unwrap_or(if entry.solid { WALL_MINIMAP } else { FLOOR_MINIMAP });
+            self.tiles[entry.id as usize] = Some(TileDef {
+                id: entry.id,
+                glyphs,
+                solid: entry.solid,
+                texture: entry.texture.as_deref().map(|t| base.join(t)),
+                faces: [face(&faces.north), face(&faces.south), face(&faces.east), face(&faces.west)],
+                minimap: (minimap[0], minimap[1], minimap[2]),
+                exit: entry.exit,
+                trigger: entry.trigger.clone(),
+                transparent: entry.transparent,
+                door: entry.door,
+                lock: entry.lock.clone(),
+                secret: entry.secret,
+                floor_link: entry.floor_link,
+                teleporter: entry.teleporter,
+                height: entry.height.unwrap_or(1.0),
+                elevation: entry.elevation,
+                segments,
+                floor_texture: entry.floor_texture.as_deref().map(|t| base.join(t)),
+                ceiling_texture: entry.ceiling_texture.as_deref().map(|t| base.join(t)),
+            });
+        }
+        Ok(())
+    }
+
+    // Textura de una cara: la propia de la cara o la general de la casilla
+    pub fn texture_for(&self, id: u8, face: Face) -> Option<&PathBuf> {
+        let tile = self.get(id)?;
+        tile.faces[face.index()].as_ref().or(tile.texture.as_ref())
+    }
+
+    pub fn get(&self, id: u8) -> Option<&TileDef> {
+        self.tiles[id as usize].as_ref()
+    }
+
+    pub fn id_for(&self, glyph: char) -> Option<u8> {
+        self.glyphs.get(&glyph).copied()
+    }
+
+    pub fn glyph_for(&self, id: u8) -> char {
+        self.get(id).map(|t| t.glyphs[0]).unwrap_or('?')
+    }
+
+    // Reemplaza la textura de todas las caras
+    pub fn set_texture(&mut self, id: u8, texture: PathBuf) {
+        if let Some(tile) = self.tiles[id as usize].as_mut() {
+            tile.texture = Some(texture);
+            tile.faces = Default::default();
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &TileDef> {
+        self.tiles.iter().flatten()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(text: &str) -> Vec<TileEntry> {
+        toml::from_str::<TileFile>(text).unwrap().tile
+    }
+
+    fn merge(text: &str) -> Result<TileSet, String> {
+        let mut tiles = (*TileSet::builtin()).clone();
+        tiles.merge(&entries(text), Path::new("nivel")).map(|_| tiles)
+    }
+
+    #[test]
+    fn overrides_replace_the_tile_and_free_its_glyphs() {
+        let tiles = merge("[[tile]]\nid = 2\nglyph = \"Qq\"\nsolid = true\ntexture = \"piedra.png\"\n").unwrap();
+        assert_eq!(tiles.id_for('Q'), Some(2));
+        assert_eq!(tiles.id_for('q'), Some(2));
+        assert_eq!(tiles.id_for('2'), None);
+        assert_eq!(tiles.glyph_for(2), 'Q');
+        assert_eq!(tiles.get(2).unwrap().texture.as_deref(), Some(Path::new("nivel/piedra.png")));
+
+        // La tabla incluida no cambia
+        assert_eq!(TileSet::builtin().id_for('2'), Some(2));
+        assert_eq!(tiles.id_for('1'), Some(1));
+    }
+
+    #[test]
+    fn repeated_ids_keep_the_last_definition() {
+        let tiles = merge("[[tile]]\nid = 40\nglyph = \"X\"\n[[tile]]\nid = 40\nglyph = \"Y\"\nexit = true\n").unwrap();
+        assert_eq!(tiles.id_for('X'), None);
+        assert_eq!(tiles.id_for('Y'), Some(40));
+        assert!(tiles.get(40).unwrap().exit);
+    }
+
+    #[test]
+    fn repeated_glyphs_are_rejected() {
+        let error = merge("[[tile]]\nid = 40\nglyph = \"Z\"\n[[tile]]\nid = 41\nglyph = \"Z\"\n").err().unwrap();
+        assert_eq!(error, "el carácter 'Z' se usa en las casillas 40 y 41");
+
+        let error = merge("[[tile]]\nid = 40\nglyph = \"1\"\n").err().unwrap();
+        assert_eq!(error, "el carácter '1' se usa en las casillas 1 y 40");
+    }
+
+    #[test]
+    fn bad_entries_are_rejected() {
+        let cases = [
+            ("id = 40\nglyph = \"\"", "la casilla 40 no tiene glyph"),
+            ("id = 40\nglyph = \"D\"\ndoor = true\nsolid = true", "la casilla 40 es puerta, no puede ser `solid`"),
+            ("id = 40\nglyph = \"S\"\nsecret = true", "la casilla 40 es secreta, tiene que ser `solid`"),
+            ("id = 40\nglyph = \"L\"\nlock = \"red\"", "la casilla 40 tiene `lock` pero no es puerta"),
+            ("id = 40\nglyph = \"L\"\ndoor = true\nlock = \"green\"", "casilla 40: llave \"green\" desconocida; colores: red, yellow, blue"),
+            ("id = 40\nglyph = \"H\"\nsolid = true\nheight = 0.0", "la casilla 40 tiene `height` o `elevation` fuera de rango"),
+            ("id = 40\nglyph = \"/\"\nsegments = [[0.0, 0.0, 2.0, 1.0]]", "la casilla 40 tiene segmentos fuera de la casilla (0..1) o de largo 0"),
+        ];
+        for (tile, expected) in cases {
+            assert_eq!(merge(&format!("[[tile]]\n{}\n", tile)).err().as_deref(), Some(expected), "{}", tile);
+        }
+    }
+
+    #[test]
+    fn unknown_fields_fail_to_parse() {
+        let error = TileSet::parse("[[tile]]\nid = 40\nglyph = \"X\"\nsolido = true\n", Path::new(".")).err().unwrap();
+        assert!(error.contains("unknown field `solido`"), "{}", error);
+    }
+}
@@ -0,0 +1,45 @@
+// watch.rs
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
+
+// Revisar el disco cada frame es innecesario; con dos veces por segundo basta
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+// Detecta cambios en archivos comparando su fecha de modificación.
+// Un archivo que aparece o desaparece también cuenta como cambio.
+pub struct FileWatcher {
+    files: Vec<(PathBuf, Option<SystemTime>)>,
+    last_poll: Instant,
+}
+
+fn modified(path: &PathBuf) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+impl FileWatcher {
+    pub fn new(paths: &[PathBuf]) -> Self {
+        FileWatcher {
+            files: paths.iter().map(|p| (p.clone(), modified(p))).collect(),
+            last_poll: Instant::now(),
+        }
+    }
+
+    // true si algún archivo cambió desde la última vez
+    pub fn poll(&mut self) -> bool {
+        if self.last_poll.elapsed() < POLL_INTERVAL {
+            return false;
+        }
+        self.last_poll = Instant::now();
+
+        let mut changed = false;
+        for (path, last) in &mut self.files {
+            let current = modified(path);
+            if current != *last {
+                *last = current;
+                changed = true;
+            }
+        }
+        changed
+    }
+}